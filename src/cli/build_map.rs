@@ -1,427 +1,526 @@
-use std::collections::VecDeque;
-use std::fs::{self, File};
-use std::io::BufReader;
-// use std::thread;
-// use std::t&ime::Duration;
-use rbx_dom_weak::WeakDom;
-use rbx_types::{Matrix3, Ref, Variant, Vector3};
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::ops::RangeInclusive;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
 
-use serde::Deserialize;
-// use indicatif::{ProgressBar, ProgressStyle};
-use anyhow::Context;
+use anyhow::{bail, Context};
+use rbx_dom_weak::WeakDom;
 use structopt::StructOpt;
-use tiny_skia::{Paint, PathBuilder, Pixmap, Transform};
 
-use crate::math_lib;
+use super::progress::ProgressBars;
+use super::GlobalOptions;
+use crate::output::raster;
+use crate::output::replace::replace_file;
+use crate::render::{
+    self, ErrorPolicy, FileKind, Framing, HeightBand, MapRenderer, RenderConfig, Scene, TileParams,
+    ViewParams,
+};
 
-const UNKNOWN_FILE_KIND_ERROR: &str = "Could not detect what kind of file to read. \
-										Expected file to end in .rbxlx or .rbxl.";
+const MISSING_SIZE_ERROR: &str =
+    "--width and --height are required unless rendering --tiles or the views in the config.";
 
-#[derive(Deserialize)]
-struct ConfigFileType {
-    draw_everything: bool,
-    world_files: Vec<ObjectFileType>,
-}
+/// Used when --quality isn't given.
+const DEFAULT_QUALITY: u8 = 90;
 
-#[derive(Deserialize)]
-struct ObjectFileType {
-    color: Vec<u8>,
-    dir: Vec<String>,
-    part_name: String,
-}
+/// How often --watch checks the place and config for changes.
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+const MISSING_VIEW_ERROR: &str =
+    "--center_x, --center_z and --scale are required when rendering a place file without --fit.";
 
 /// Generates an image file representing a game map
 #[derive(Debug, StructOpt)]
 pub struct BuildMapCommand {
-    /// Path to the place file
+    /// Path to the place or model file
     ///
-    /// Should end in .rbxl or .rbxlx
+    /// Should end in .rbxl, .rbxlx, .rbxm or .rbxmx
     #[structopt(long, short)]
     pub placefile: PathBuf,
 
     /// The Z height of the image in studs
     #[structopt(long = "height")]
-    pub height: i32,
+    pub height: Option<u32>,
 
     /// The X width of the image in studs
     #[structopt(long = "width")]
-    pub width: i32,
+    pub width: Option<u32>,
 
     /// The X Center of the image in world space
     #[structopt(long = "center_x")]
-    pub center_x: f32,
+    pub center_x: Option<f32>,
 
     /// The Z Center of the image in world space
     #[structopt(long = "center_z")]
-    pub center_z: f32,
+    pub center_z: Option<f32>,
 
     /// scale
+    ///
+    /// Model files and --fit renders work this out themselves, so this,
+    /// --center_x and --center_z are only required otherwise.
     #[structopt(long = "scale")]
-    pub scale: f32,
+    pub scale: Option<f32>,
+
+    /// Frame every selected part inside the image automatically
+    #[structopt(long = "fit")]
+    pub fit: bool,
+
+    /// Empty space in pixels to leave around a fitted render
+    #[structopt(long = "margin", default_value = "0")]
+    pub margin: f32,
 
     /// config
     #[structopt(long = "config")]
     pub config: PathBuf,
+
+    /// Where to write the rendered image
+    ///
+    /// The format is picked from the extension: .png, .jpg, .jpeg, .webp,
+    /// .bmp, .tif, .tiff or .svg for a vector image
+    #[structopt(long = "output", short, default_value = "output.png")]
+    pub output: PathBuf,
+
+    /// Quality of lossy output, from 1 to 100 (defaults to 90)
+    ///
+    /// Used by JPEG, and by WebP when built with the webp-encoder feature.
+    /// Other formats are lossless and ignore it.
+    #[structopt(long = "quality")]
+    pub quality: Option<u8>,
+
+    /// Render a pyramid of map tiles into this folder instead of a single
+    /// image
+    ///
+    /// Tiles are written as {z}/{x}/{y}.png, ready for web map viewers. The
+    /// pyramid always covers everything selected, so the view arguments are
+    /// not needed.
+    #[structopt(long = "tiles")]
+    pub tiles: Option<PathBuf>,
+
+    /// Width and height of each tile in pixels
+    #[structopt(long = "tile-size", default_value = "256")]
+    pub tile_size: u32,
+
+    /// The most zoomed out level to write, where 0 is a single tile
+    #[structopt(long = "min-zoom", default_value = "0")]
+    pub min_zoom: u8,
+
+    /// The most zoomed in level to write
+    #[structopt(long = "max-zoom", default_value = "5")]
+    pub max_zoom: u8,
+
+    /// Also write a 16 bit grayscale heightmap of the same view here
+    ///
+    /// Each pixel holds the height of the highest surface under it, with 0
+    /// meaning nothing is there. Should end in .png, .tif or .tiff.
+    #[structopt(long = "heightmap")]
+    pub heightmap: Option<PathBuf>,
+
+    /// Height in studs that maps to the darkest heightmap pixel. Defaults to
+    /// the lowest surface drawn.
+    #[structopt(long = "heightmap-min")]
+    pub heightmap_min: Option<f32>,
+
+    /// Height in studs that maps to the brightest heightmap pixel. Defaults
+    /// to the highest surface drawn.
+    #[structopt(long = "heightmap-max")]
+    pub heightmap_max: Option<f32>,
+
+    /// Leave out everything entirely below this height in studs
+    ///
+    /// Replaces min_y in the config. Rules with a min_y of their own keep it.
+    #[structopt(long = "min-y", allow_hyphen_values = true)]
+    pub min_y: Option<f32>,
+
+    /// Leave out everything entirely above this height in studs
+    ///
+    /// Replaces max_y in the config. Rules with a max_y of their own keep it.
+    #[structopt(long = "max-y", allow_hyphen_values = true)]
+    pub max_y: Option<f32>,
+
+    /// How many threads to render with. Defaults to one per CPU core
+    ///
+    /// The image comes out the same whatever this is set to.
+    #[structopt(long = "threads")]
+    pub threads: Option<usize>,
+
+    /// Keep running, and render again whenever the place or config file
+    /// changes
+    ///
+    /// If a render fails, the error is shown and the last image is left as
+    /// it was.
+    #[structopt(long = "watch")]
+    pub watch: bool,
+
+    /// What to do with instances that can't be drawn. Valid values are skip
+    /// and fail.
+    #[structopt(long = "on-error", default_value = "skip")]
+    pub on_error: ErrorPolicy,
 }
 
 impl BuildMapCommand {
-    pub fn run(self) -> anyhow::Result<()> {
+    pub fn run(self, global: &GlobalOptions) -> anyhow::Result<()> {
+        if let Some(threads) = self.threads {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build_global()
+                .context("Could not start the render threads")?;
+        }
+
+        let progress = Arc::new(ProgressBars::new(global.color));
+        if self.watch {
+            self.watch(&progress)
+        } else {
+            self.render(&progress)
+        }
+    }
+
+    /// Renders again every time the place or config changes, until the
+    /// process is stopped.
+    fn watch(&self, progress: &Arc<ProgressBars>) -> anyhow::Result<()> {
+        loop {
+            // read before rendering, so saves made during a render aren't
+            // missed.
+            let seen = self.modified_times();
+
+            if let Err(error) = self.render(progress) {
+                log::error!("{:?}", error);
+                log::warn!("The last image was kept.");
+            }
+
+            println!(
+                "Watching {} and {} for changes..",
+                self.placefile.display(),
+                self.config.display()
+            );
+            self.wait_for_change(seen);
+        }
+    }
+
+    /// Waits until the place or config is different from when `seen` was
+    /// read, and has then stopped changing.
+    fn wait_for_change(&self, seen: [Option<SystemTime>; 2]) {
+        while self.modified_times() == seen {
+            thread::sleep(WATCH_INTERVAL);
+        }
+
+        // large places take a while to save, so the files are only read once
+        // they have settled.
+        loop {
+            let current = self.modified_times();
+            thread::sleep(WATCH_INTERVAL);
+            if self.modified_times() == current {
+                return;
+            }
+        }
+    }
+
+    /// When the place and config were last changed, if they can be read.
+    fn modified_times(&self) -> [Option<SystemTime>; 2] {
+        let modified = |path: &Path| fs::metadata(path).and_then(|x| x.modified()).ok();
+        [modified(&self.placefile), modified(&self.config)]
+    }
+
+    /// Reads the config and place and renders them once.
+    fn render(&self, progress: &Arc<ProgressBars>) -> anyhow::Result<()> {
         log::trace!("Determining file type");
         println!("building..");
-        let file_type = detect_file_kind(&self.placefile).context(UNKNOWN_FILE_KIND_ERROR)?;
-
-        let file_reader = BufReader::new(File::open(&self.placefile)?);
-
-        let config_raw_data = fs::read_to_string(self.config).expect("Invalid config file path");
-        let config_data: ConfigFileType =
-            serde_json::from_str(&config_raw_data).expect("JSON formatting issue with config file");
-
-        //let mut world_pixel_data: Vec<Vec<i32>> = vec![vec![Default::default(); self.height.try_into().unwrap()]; self.width.try_into().unwrap()];
-
-        //let mut one_dimensional_pixel_data: Vec<i32> = Vec::with_capacity((self.height * self.width).try_into().unwrap());
-
-        match file_type {
-            OutputKind::Rbxl => {
-                let dom = rbx_binary::from_reader(file_reader)?;
-                // get binary working first, then xml.
-                // debug:
-                println!("Root instances in file:");
-                let root = dom.root();
-
-                let mut pixmap = Pixmap::new(self.width as u32, self.height as u32).unwrap();
-                if config_data.draw_everything {
-                    // draw everything :)
-                    let workspace = root
-                        .children()
-                        .iter()
-                        .find(|&&x| dom.get_by_ref(x).unwrap().name == "Workspace")
-                        .context("Could not find workspace from file.")?;
-                    let descendants = get_descendants(&dom, workspace)?;
-
-                    for iref in descendants.iter() {
-                        let part = dom.get_by_ref(*iref).unwrap();
-                        if part.class == "Part" {
-                            let cf = match part.properties.get("CFrame") {
-                                Some(Variant::CFrame(v)) => v,
-                                _ => panic!("Part does not have a cframe"),
-                            };
-
-                            let object_size = match part.properties.get("Size") {
-                                Some(Variant::Vector3(v)) => v,
-                                _ => panic!("Part does not have a size"),
-                            };
-
-                            let object_color = match part.properties.get("Color") {
-                                Some(Variant::Color3uint8(v)) => v,
-                                _ => panic!("Part does not have a color"),
-                            };
-
-                            let object_transparency = match part.properties.get("Transparency") {
-                                Some(Variant::Float32(v)) => v,
-                                _ => panic!("Part does not have transparency"),
-                            };
-
-                            let object_position =
-                                Vector3::new(cf.position.x, cf.position.y, cf.position.z); //cf.position + Vector3::new(0f32, 0f32, 5000f32);
-                            let object_orientation = cf.orientation;
-
-                            let color: Vec<u8> = vec![
-                                object_color.r,
-                                object_color.g,
-                                object_color.b,
-                                ((1f32 - object_transparency) * 255f32).round() as u8,
-                            ];
-
-                            //let mut c: [u8; 3] = [object_color.r, object_color.g, object_color.b];
-
-                            let r_p = Vector3::new(
-                                object_position.x * self.scale + self.center_x,
-                                object_position.y * self.scale,
-                                object_position.z * self.scale + self.center_z,
-                            );
-                            let s = Vector3::new(
-                                object_size.x * self.scale,
-                                object_size.y * self.scale,
-                                object_size.z * self.scale,
-                            );
-                            //draw_part_to_imgbuf(&mut img, r_p, s, object_orientation, &c);
-                            draw_part_on_pixmap(&mut pixmap, r_p, s, object_orientation, &color);
-                        }
-                    }
-                } else {
-                    // get world files and iterate through.
-                    let world_data_files = config_data.world_files;
-                    for object_data_file in world_data_files.iter() {
-                        let object_part_name = &object_data_file.part_name;
-                        let dir_path = &object_data_file.dir;
-                        let mut inst = dom.root();
-                        let mut inst_ref: &Ref = &Ref::new();
-                        let mut stack = VecDeque::from_iter(dir_path.iter());
-                        while let Some(cur_path) = stack.pop_front() {
-                            inst_ref = inst
-                                .children()
-                                .iter()
-                                .find(|&&x| dom.get_by_ref(x).unwrap().name == *cur_path)
-                                .unwrap_or_else(|| panic!("Unable to find instance {}", cur_path));
-                            inst = dom.get_by_ref(*inst_ref).unwrap();
-                        }
-                        let descendants = get_descendants(&dom, inst_ref)?;
-
-                        for iref in descendants.iter() {
-                            let part = dom.get_by_ref(*iref).unwrap();
-                            //println!("{}", part.name);
-                            if &part.name == object_part_name {
-                                //println!("did part");
-                                let cf = match part.properties.get("CFrame") {
-                                    Some(Variant::CFrame(v)) => v,
-                                    _ => panic!("Part does not have a cframe"),
-                                };
-
-                                let object_size = match part.properties.get("Size") {
-                                    Some(Variant::Vector3(v)) => v,
-                                    _ => panic!("Part does not have a size"),
-                                };
-
-                                let object_position =
-                                    Vector3::new(cf.position.x, cf.position.y, cf.position.z); //cf.position + Vector3::new(0f32, 0f32, 5000f32);
-                                let object_orientation = cf.orientation;
-
-                                let r_p = Vector3::new(
-                                    object_position.x * self.scale + self.center_x,
-                                    object_position.y * self.scale,
-                                    object_position.z * self.scale + self.center_z,
-                                );
-                                let s = Vector3::new(
-                                    object_size.x * self.scale,
-                                    object_size.y * self.scale,
-                                    object_size.z * self.scale,
-                                );
-                                draw_part_on_pixmap(
-                                    &mut pixmap,
-                                    r_p,
-                                    s,
-                                    object_orientation,
-                                    &object_data_file.color,
-                                );
-                            }
-                        }
-
-                        println!("Should do {}", inst.name);
-                    }
-                }
-                // let workspace = root.children().iter()
-                // 					.find(|&&x| dom.get_by_ref(x).unwrap().name == "Workspace")
-                // 					.context("Could not find workspace from file.")?;
-                // println!("Found Workspace: {}", dom.get_by_ref(*workspace).unwrap().name);
-                // // Attempt to find the map contents
-                // let map_contents = dom
-                // 					.get_by_ref(*workspace)
-                // 					.unwrap().children().iter()
-                // 					.find(|&&x| dom.get_by_ref(x).unwrap().name == "Map")
-                // 					.context("Could not find map folder in workspace.")?;
-                // println!("Found map folder in workspace. {}", dom.get_by_ref(*map_contents).unwrap().name);
-                // let road_folder = dom
-                // 					.get_by_ref(*map_contents)
-                // 					.unwrap().children().iter()
-                // 					.find(|&&x| dom.get_by_ref(x).unwrap().name == "Roads")
-                // 					.context("Could not find road folder in map.")?;
-                // println!("Found road folder in map. {}", dom.get_by_ref(*road_folder).unwrap().name);
-
-                // //let mut document = Document::new().set("viewBox", (0, 0, self.width, self.height));
-
-                // // let terrain_ref = dom
-                // // 					.get_by_ref(*workspace)
-                // // 					.unwrap().children().iter()
-                // // 					.find(|&&x| dom.get_by_ref(x).unwrap().name == "Terrain")
-                // // 					.context("Could not find terrain in workspace.")?;
-                // // //println!("{:?}", dom.get_by_ref(*terrain_ref).unwrap().properties.get("SmoothGrid"));
-                // // let terrain = dom.get_by_ref(*terrain_ref).unwrap();
-                // // let grid = match terrain.properties.get("SmoothGrid") {
-                // // 	Some(Variant::BinaryString(v)) => v,
-                // // 	_ => panic!("No grid?"),
-                // // };
-
-                // for &referent in dom.get_by_ref(*road_folder).unwrap().children() {
-                // 	let road_model = dom.get_by_ref(referent).unwrap();
-                // 	// now we need to create an SVG shape for every part named 'base'
-                // 	for &iref in road_model.children() {
-                // 		let instance = dom.get_by_ref(iref).unwrap();
-                // 		if instance.name == "Base" {
-                // 			// we care about this, draw it.
-                // 			// okay so how tf do we get instance parameters from this shit.
-                // 			//println!("{:?}", instance.properties.keys());
-                // 			let cf = match instance.properties.get("CFrame") {
-                // 				Some(Variant::CFrame(v)) => v,
-                // 				_ => panic!("Part does not have a cframe"),
-                // 			};
-
-                // 			let object_size = match instance.properties.get("Size") {
-                // 				Some(Variant::Vector3(v)) => v,
-                // 				_ => panic!("Part does not have a size"),
-                // 			};
-
-                // 			let object_position = Vector3::new(cf.position.x, cf.position.y, cf.position.z);//cf.position + Vector3::new(0f32, 0f32, 5000f32);
-                // 			let object_orientation = cf.orientation;
-
-                // 			let r_p = Vector3::new(object_position.x * self.scale + self.center_x, object_position.y * self.scale, object_position.z * self.scale + self.center_z);
-                // 			let s = Vector3::new(object_size.x * self.scale, object_size.y * self.scale, object_size.z * self.scale);
-                // 			draw_part_on_pixmap(&mut pixmap, r_p, s, object_orientation);
-                // 		}
-                // 	}
-                // }
-
-                //render::save(&canvas, "test.svg", SvgRenderer::new()).expect("Failed to save.");
-                // let mut output_file = File::create("test2.svg")?;
-                // output_file.write_all(&document.to_string().into_bytes());
-
-                println!("Saving..");
-                pixmap.save_png("output.png").unwrap();
-
-                println!("Success.");
-                // for &referent in dom.root().children() {
-                // 	let instance = dom.get_by_ref(referent).unwrap();
-                // 	println!("- {}", instance.name);
-                // }
+
+        let mut config = RenderConfig::from_path(&self.config)?;
+        if self.min_y.is_some() || self.max_y.is_some() {
+            config.band = HeightBand {
+                min_y: self.min_y,
+                max_y: self.max_y,
+            }
+            .or(&config.band);
+            config.validate()?;
+        }
+        self.check_outputs(&config)?;
+
+        let renderer = MapRenderer::new(config)
+            .with_error_policy(self.on_error)
+            .with_progress(progress.clone());
+
+        let (dom, kind) = render::load_dom_with_progress(&self.placefile, &**progress)?;
+
+        if let Some(tiles) = &self.tiles {
+            return self.write_tiles(&renderer, &dom, kind, tiles);
+        }
+
+        if !renderer.config().views.is_empty() {
+            return self.write_views(&renderer, &dom, kind);
+        }
+
+        let params = ViewParams {
+            width: self.width.context(MISSING_SIZE_ERROR)?,
+            height: self.height.context(MISSING_SIZE_ERROR)?,
+            framing: self.framing(kind.is_model())?,
+        };
+        let scene = renderer.build_scene(&dom, kind, &params)?;
+
+        println!("Saving..");
+        self.save_scene(&scene, &self.output)?;
+
+        if let Some(path) = &self.heightmap {
+            self.write_heightmap(&scene, path)?;
+        }
+
+        println!("Success.");
+        Ok(())
+    }
+
+    /// Catches arguments that can't be written out before the place is
+    /// decoded, which can take a while for large places.
+    fn check_outputs(&self, config: &RenderConfig) -> anyhow::Result<()> {
+        raster::check_quality(self.quality())?;
+
+        if self.tiles.is_some() {
+            if self.heightmap.is_some() {
+                bail!("--heightmap can't be used together with --tiles.");
             }
-            OutputKind::Rbxlx => {
-                //let dom = rbx_xml::from_reader_default(file_reader)?;
+            // tiles are always PNG.
+            self.warn_unused_quality(&[]);
+            return Ok(());
+        }
+
+        if !config.views.is_empty() {
+            if self.heightmap.is_some() {
+                bail!("--heightmap can't be used with a config that has views.");
+            }
+            for view in &config.views {
+                check_image_output(&view.output)
+                    .with_context(|| format!("View {} can't be written", view.name))?;
             }
+            let outputs: Vec<&Path> = config
+                .views
+                .iter()
+                .map(|view| view.output.as_path())
+                .collect();
+            self.warn_unused_quality(&outputs);
+            return Ok(());
         }
-        // println!("Rendering map");
-        // let pb = ProgressBar::new(200);
-        // for _ in 0..200 {
-        // 	pb.inc(1);
-        // 	thread::sleep(Duration::from_millis(5));
-        // }
-        // pb.finish_with_message("done");
+
+        check_image_output(&self.output)?;
+        if let Some(path) = &self.heightmap {
+            raster::heightmap_format(path)?;
+        }
+        if let (Some(low), Some(high)) = (self.heightmap_min, self.heightmap_max) {
+            if low > high {
+                bail!(
+                    "--heightmap-min ({}) can't be above --heightmap-max ({}).",
+                    low,
+                    high
+                );
+            }
+        }
+        self.warn_unused_quality(&[&self.output]);
         Ok(())
     }
-}
 
-/// The different file types we support
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum OutputKind {
-    /// An XML place file
-    Rbxlx,
-    /// A binary place file
-    Rbxl,
-}
+    fn quality(&self) -> u8 {
+        self.quality.unwrap_or(DEFAULT_QUALITY)
+    }
 
-fn detect_file_kind(output: &Path) -> Option<OutputKind> {
-    let extension = output.extension()?.to_str()?;
+    /// Lets the user know when --quality was given but none of the images
+    /// being written are lossy.
+    fn warn_unused_quality(&self, outputs: &[&Path]) {
+        if self.quality.is_some() && !outputs.iter().any(|path| raster::uses_quality(path)) {
+            log::warn!(
+                "--quality only applies to {} output, so it is ignored.",
+                raster::QUALITY_FORMATS
+            );
+        }
+    }
 
-    match extension {
-        "rbxlx" => Some(OutputKind::Rbxlx),
-        "rbxl" => Some(OutputKind::Rbxl),
-        _ => None,
+    /// Writes a scene as an SVG or a raster image, depending on the
+    /// extension of `path`.
+    ///
+    /// The old image stays in place until the new one is completely written.
+    fn save_scene(&self, scene: &Scene, path: &Path) -> anyhow::Result<()> {
+        if is_svg(path) {
+            let document = scene.to_svg();
+            replace_file(path, |writer| Ok(svg::write(writer, &document)?))
+        } else {
+            let format = raster::image_format(path)?;
+            let pixmap = scene.draw();
+            replace_file(path, |writer| {
+                raster::write_pixmap(&pixmap, format, writer, self.quality())
+            })
+        }
     }
-}
 
-// fn draw_part_to_imgbuf_experimental(
-//     imgbuf: &mut RgbImage,
-//     pos: Vector3,
-//     size: Vector3,
-//     rot: Matrix3,
-//     color: &[u8; 3],
-// ) {
-//     let aa = math_lib::axis_angle_conversion::matrix3_to_axis_angle(rot);
-//     let t = aa.y;
-//     // 1 1
-//     let r1_x = size.x * 0.5f32 * t.cos() - size.z * 0.5f32 * t.sin() + pos.x;
-//     let r1_z = size.x * 0.5f32 * t.sin() + size.z * 0.5f32 * t.cos() + pos.z;
-//     // -1 1
-//     let r2_x = -size.x * 0.5f32 * t.cos() - size.z * 0.5f32 * t.sin() + pos.x;
-//     let r2_z = -size.x * 0.5f32 * t.sin() + size.z * 0.5f32 * t.cos() + pos.z;
-//     // 1 -1
-//     let r3_x = -size.x * 0.5f32 * t.cos() + size.z * 0.5f32 * t.sin() + pos.x;
-//     let r3_z = -size.x * 0.5f32 * t.sin() - size.z * 0.5f32 * t.cos() + pos.z;
-//     // -1 -1
-//     let r4_x = size.x * 0.5f32 * t.cos() + size.z * 0.5f32 * t.sin() + pos.x;
-//     let r4_z = size.x * 0.5f32 * t.sin() - size.z * 0.5f32 * t.cos() + pos.z;
-
-//     imgbuf.get_pixel_mut(r1_x as u32, r1_z as u32).0 = *color;
-//     imgbuf.get_pixel_mut(r2_x as u32, r2_z as u32).0 = *color;
-//     imgbuf.get_pixel_mut(r3_x as u32, r3_z as u32).0 = *color;
-//     imgbuf.get_pixel_mut(r4_x as u32, r4_z as u32).0 = *color;
-
-//     let filler_scale = 2u32;
-
-//     let img_size_x = (r3_x - r1_x).round() as u32 * filler_scale;
-//     let img_size_z = (r3_z - r1_z).round() as u32 * filler_scale;
-//     for x_s in 0u32..img_size_x {
-//         let x = lerp(r1_x, r3_x, (x_s / img_size_x) as f32) as u32;
-//         for z_s in 0u32..img_size_z {
-//             let z = lerp(r1_z, r3_z, (z_s / img_size_z) as f32) as u32;
-//             imgbuf.get_pixel_mut(x, z).0 = *color;
-//         }
-//     }
-// }
-
-// actual rendering code
-fn draw_part_on_pixmap(map: &mut Pixmap, pos: Vector3, size: Vector3, rot: Matrix3, color: &[u8]) {
-    let aa = math_lib::axis_angle_conversion::matrix3_to_axis_angle(rot);
-    let t = aa.y;
-    // 1 1
-    let r1_x = size.x * 0.5f32 * t.cos() - size.z * 0.5f32 * t.sin() + pos.x;
-    let r1_z = size.x * 0.5f32 * t.sin() + size.z * 0.5f32 * t.cos() + pos.z;
-    // -1 1
-    let r2_x = -size.x * 0.5f32 * t.cos() - size.z * 0.5f32 * t.sin() + pos.x;
-    let r2_z = -size.x * 0.5f32 * t.sin() + size.z * 0.5f32 * t.cos() + pos.z;
-    // 1 -1
-    let r3_x = -size.x * 0.5f32 * t.cos() + size.z * 0.5f32 * t.sin() + pos.x;
-    let r3_z = -size.x * 0.5f32 * t.sin() - size.z * 0.5f32 * t.cos() + pos.z;
-    // -1 -1
-    let r4_x = size.x * 0.5f32 * t.cos() + size.z * 0.5f32 * t.sin() + pos.x;
-    let r4_z = size.x * 0.5f32 * t.sin() - size.z * 0.5f32 * t.cos() + pos.z;
-
-    let mut pb = PathBuilder::new();
-    pb.move_to(r1_x, r1_z);
-    pb.line_to(r2_x, r2_z);
-    pb.line_to(r3_x, r3_z);
-    pb.line_to(r4_x, r4_z);
-    //pb.line_to(r1_x, r1_z);
-    pb.close();
-    //pb.finish().unwrap()
-    let obj = pb.finish();
-    if let Some(path) = obj {
-        let mut paint = Paint::default();
-        paint.set_color_rgba8(color[0], color[1], color[2], color[3]);
-        paint.anti_alias = true;
-
-        map.fill_path(
-            &path,
-            &paint,
-            tiny_skia::FillRule::Winding,
-            Transform::identity(),
-            None,
+    /// Renders every view in the config from the one decoded file.
+    fn write_views(
+        &self,
+        renderer: &MapRenderer,
+        dom: &WeakDom,
+        kind: FileKind,
+    ) -> anyhow::Result<()> {
+        let views = &renderer.config().views;
+        for (index, view) in views.iter().enumerate() {
+            println!(
+                "Rendering view {} ({}/{})..",
+                view.name,
+                index + 1,
+                views.len()
+            );
+
+            let scene = renderer
+                .for_view(view)
+                .build_scene(dom, kind, &view.params()?)
+                .with_context(|| format!("Could not render view {}", view.name))?;
+
+            if let Some(parent) = view.output.parent() {
+                fs::create_dir_all(parent)
+                    .with_context(|| format!("Could not create {}", parent.display()))?;
+            }
+            self.save_scene(&scene, &view.output)?;
+        }
+
+        println!("Wrote {} views.", views.len());
+        println!("Success.");
+        Ok(())
+    }
+
+    /// Writes the heightmap of a scene, and says which heights it covers so
+    /// its pixels can be turned back into studs.
+    fn write_heightmap(&self, scene: &Scene, path: &Path) -> anyhow::Result<()> {
+        let heightmap = scene.heightmap();
+        let (low, high) = heightmap.range().unwrap_or((0f32, 0f32));
+        let low = self.heightmap_min.unwrap_or(low);
+        let high = self.heightmap_max.unwrap_or(high);
+
+        let format = raster::heightmap_format(path)?;
+        let image = heightmap.to_image(low, high);
+        replace_file(path, |writer| {
+            raster::write_heightmap(&image, format, writer)
+        })?;
+        println!(
+            "Heightmap covers Y {} to {} (pixel values 1 to 65535).",
+            low, high
         );
+        Ok(())
+    }
+
+    /// Renders the tile pyramid and writes every tile under `root`.
+    fn write_tiles(
+        &self,
+        renderer: &MapRenderer,
+        dom: &WeakDom,
+        kind: FileKind,
+        root: &Path,
+    ) -> anyhow::Result<()> {
+        let params = TileParams {
+            tile_size: self.tile_size,
+            min_zoom: self.min_zoom,
+            max_zoom: self.max_zoom,
+        };
+        let pyramid = renderer.build_tiles(dom, kind, &params)?;
+
+        println!("Saving..");
+        let paths = Mutex::new(HashSet::new());
+        let written = pyramid.for_each_tile(|id, pixmap| -> anyhow::Result<()> {
+            let path = root.join(id.path("png"));
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)
+                    .with_context(|| format!("Could not create {}", parent.display()))?;
+            }
+            let format = raster::image_format(&path)?;
+            replace_file(&path, |writer| {
+                raster::write_pixmap(&pixmap, format, writer, self.quality())
+            })?;
+            paths.lock().unwrap().insert(path);
+            Ok(())
+        })?;
+
+        println!("Wrote {} tiles to {}.", written, root.display());
+        let removed = remove_stale_tiles(
+            root,
+            self.min_zoom..=self.max_zoom,
+            &paths.into_inner().unwrap(),
+        )?;
+        if removed > 0 {
+            println!("Removed {} tiles left from an earlier render.", removed);
+        }
+        println!("Success.");
+        Ok(())
+    }
+
+    /// Works out how to place the world in the image from the arguments.
+    ///
+    /// Models are always fitted, so they don't need a center or scale.
+    fn framing(&self, is_model: bool) -> anyhow::Result<Framing> {
+        if self.fit || is_model {
+            return Ok(Framing::Fit {
+                margin: self.margin,
+            });
+        }
+
+        Ok(Framing::Manual {
+            offset_x: self.center_x.context(MISSING_VIEW_ERROR)?,
+            offset_z: self.center_z.context(MISSING_VIEW_ERROR)?,
+            scale: self.scale.context(MISSING_VIEW_ERROR)?,
+        })
     }
 }
 
-fn get_descendants(dom: &WeakDom, inst_ref: &Ref) -> anyhow::Result<Vec<Ref>> {
-    let instance = dom
-        .get_by_ref(*inst_ref)
-        .expect("received invalid child in tree when recursing through descendants");
+fn is_svg(path: &Path) -> bool {
+    path.extension().and_then(|x| x.to_str()) == Some("svg")
+}
 
-    let mut descendants: Vec<Ref> = Vec::new();
-    let mut stack = VecDeque::from_iter(instance.children().iter());
+/// Fails if an image can't be written to `path`, which is either an SVG or
+/// one of the raster formats.
+fn check_image_output(path: &Path) -> anyhow::Result<()> {
+    if !is_svg(path) {
+        raster::image_format(path)?;
+    }
 
-    while let Some(current) = stack.pop_front() {
-        descendants.push(*current);
+    Ok(())
+}
 
-        let current_instance = dom
-            .get_by_ref(*current)
-            .expect("received invalid child in tree when recursing through descendants");
+/// Deletes the tiles at `zooms` that weren't just `written`, so the pyramid
+/// doesn't keep parts that have since been removed. Folders left empty are
+/// deleted too.
+fn remove_stale_tiles(
+    root: &Path,
+    zooms: RangeInclusive<u8>,
+    written: &HashSet<PathBuf>,
+) -> anyhow::Result<usize> {
+    let list = |folder: &Path| -> anyhow::Result<Vec<PathBuf>> {
+        match fs::read_dir(folder) {
+            Ok(entries) => entries
+                .map(|entry| Ok(entry?.path()))
+                .collect::<io::Result<_>>()
+                .with_context(|| format!("Could not read {}", folder.display())),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(error) => {
+                Err(error).with_context(|| format!("Could not read {}", folder.display()))
+            }
+        }
+    };
 
-        for child in current_instance.children().iter().rev() {
-            stack.push_front(child);
+    let mut removed = 0;
+    for z in zooms {
+        for column in list(&root.join(z.to_string()))? {
+            if !column.is_dir() {
+                continue;
+            }
+
+            for tile in list(&column)? {
+                let is_tile = tile.extension().and_then(|x| x.to_str()) == Some("png");
+                if is_tile && !written.contains(&tile) {
+                    fs::remove_file(&tile)
+                        .with_context(|| format!("Could not remove {}", tile.display()))?;
+                    removed += 1;
+                }
+            }
+
+            // fails, and is left alone, when anything is still in it.
+            let _ = fs::remove_dir(&column);
         }
     }
 
-    Ok(descendants)
+    Ok(removed)
 }
-
-// fn lerp(a: f32, b: f32, t: f32) -> f32 {
-//     a + (b - a) * t
-// }
@@ -0,0 +1,93 @@
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+
+use anyhow::Context;
+use structopt::StructOpt;
+
+use super::progress::ProgressBars;
+use super::GlobalOptions;
+use crate::dom_lib::class;
+use crate::dom_lib::outline::{outline, OutlineNode, OutlineOptions};
+use crate::render;
+
+/// Prints the instance tree of a place or model, to help with writing the
+/// `dir` of config rules
+#[derive(Debug, StructOpt)]
+pub struct InspectCommand {
+    /// Path to the place or model file
+    ///
+    /// Should end in .rbxl, .rbxlx, .rbxm or .rbxmx
+    #[structopt(long, short)]
+    pub placefile: PathBuf,
+
+    /// How many levels of instances to show, where 1 is only the top level
+    /// (eg. Workspace and Lighting)
+    #[structopt(long = "depth")]
+    pub depth: Option<usize>,
+
+    /// Only show instances of this class, or of classes inheriting from it,
+    /// along with the instances leading to them. Can be given more than once.
+    #[structopt(long = "class")]
+    pub classes: Vec<String>,
+
+    /// Print the tree as JSON instead of indented text
+    #[structopt(long = "json")]
+    pub json: bool,
+}
+
+impl InspectCommand {
+    pub fn run(self, global: &GlobalOptions) -> anyhow::Result<()> {
+        for class_name in self.classes.iter() {
+            if !class::is_known(class_name) {
+                log::warn!(
+                    "{} is not a known class, so nothing will match it.",
+                    class_name
+                );
+            }
+        }
+
+        let progress = ProgressBars::new(global.color);
+        let (dom, _) = render::load_dom_with_progress(&self.placefile, &progress)?;
+
+        let options = OutlineOptions {
+            max_depth: self.depth,
+            classes: self.classes,
+        };
+        let nodes = outline(&dom, dom.root(), &options);
+
+        let stdout = io::stdout();
+        let mut out = BufWriter::new(stdout.lock());
+        let printed = if self.json {
+            serde_json::to_writer_pretty(&mut out, &nodes)
+                .map_err(io::Error::from)
+                .and_then(|_| writeln!(out))
+        } else {
+            nodes
+                .iter()
+                .try_for_each(|node| print_node(&mut out, node, 0))
+        };
+
+        // the tree is often piped into head or less, which can close early.
+        match printed.and_then(|_| out.flush()) {
+            Err(error) if error.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+            printed => printed.context("Could not print the tree"),
+        }
+    }
+}
+
+fn print_node(out: &mut impl Write, node: &OutlineNode, indent: usize) -> io::Result<()> {
+    writeln!(
+        out,
+        "{}{} ({}) - {} parts",
+        "  ".repeat(indent),
+        node.name,
+        node.class,
+        node.parts
+    )?;
+
+    for child in node.children.iter() {
+        print_node(out, child, indent + 1)?;
+    }
+
+    Ok(())
+}
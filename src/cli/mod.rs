@@ -1,4 +1,6 @@
 mod build_map;
+mod inspect;
+mod progress;
 mod test;
 
 use std::str::FromStr;
@@ -8,6 +10,7 @@ use thiserror::Error;
 use structopt::StructOpt;
 
 pub use self::build_map::BuildMapCommand;
+pub use self::inspect::InspectCommand;
 pub use self::test::TestCommand;
 
 #[derive(Debug, StructOpt)]
@@ -24,7 +27,8 @@ impl Options {
     pub fn run(self) -> anyhow::Result<()> {
         match self.subcommand {
             Subcommand::Test(subcommand) => subcommand.run(),
-            Subcommand::BuildMap(subcommand) => subcommand.run(),
+            Subcommand::BuildMap(subcommand) => subcommand.run(&self.global),
+            Subcommand::Inspect(subcommand) => subcommand.run(&self.global),
         }
     }
 }
@@ -88,8 +92,12 @@ pub struct ColorChoiceParseError {
     attempted: String,
 }
 
+// only ever built once, so the size of the build-map arguments doesn't
+// matter.
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, StructOpt)]
 pub enum Subcommand {
     Test(TestCommand),
     BuildMap(BuildMapCommand),
+    Inspect(InspectCommand),
 }
@@ -0,0 +1,89 @@
+use std::sync::RwLock;
+
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+
+use super::ColorChoice;
+use crate::render::{Progress, Stage};
+
+/// Shows render progress as bars on stderr, one stage at a time.
+///
+/// indicatif already keeps the bars off stderr when it isn't a terminal, so
+/// piped and logged runs stay clean.
+pub struct ProgressBars {
+    color: ColorChoice,
+    bar: RwLock<Option<ProgressBar>>,
+}
+
+impl ProgressBars {
+    pub fn new(color: ColorChoice) -> ProgressBars {
+        ProgressBars {
+            color,
+            bar: RwLock::new(None),
+        }
+    }
+
+    fn style(&self, stage: Stage, has_total: bool) -> ProgressStyle {
+        let bar = match self.color {
+            ColorChoice::Never => "{bar:40}",
+            ColorChoice::Auto | ColorChoice::Always => "{bar:40.cyan/blue}",
+        };
+        let count = match stage {
+            Stage::Decoding => "{bytes}/{total_bytes}",
+            Stage::Collecting | Stage::Drawing | Stage::DrawingTiles => "{pos}/{len}",
+        };
+
+        if has_total {
+            ProgressStyle::default_bar()
+                .template(&format!("{{msg:>10}} {} {} ({{eta}})", bar, count))
+                .progress_chars("=> ")
+        } else {
+            ProgressStyle::default_spinner().template("{msg:>10} {spinner} {pos}")
+        }
+    }
+}
+
+impl Progress for ProgressBars {
+    fn start(&self, stage: Stage, total: Option<u64>) {
+        let message = match stage {
+            Stage::Decoding => "Decoding",
+            Stage::Collecting => "Collecting",
+            Stage::Drawing => "Drawing",
+            Stage::DrawingTiles => "Tiles",
+        };
+
+        let bar = match total {
+            Some(total) => ProgressBar::with_draw_target(total, ProgressDrawTarget::stderr()),
+            None => {
+                let bar = ProgressBar::new_spinner();
+                bar.set_draw_target(ProgressDrawTarget::stderr());
+                bar
+            }
+        };
+        bar.set_style(self.style(stage, total.is_some()));
+        bar.set_message(message);
+
+        // a stage that was never finished, eg. because it failed, is left
+        // where it stopped.
+        if let Some(previous) = self.bar.write().unwrap().replace(bar) {
+            previous.abandon();
+        }
+    }
+
+    fn grow(&self, steps: u64) {
+        if let Some(bar) = &*self.bar.read().unwrap() {
+            bar.inc_length(steps);
+        }
+    }
+
+    fn advance(&self, steps: u64) {
+        if let Some(bar) = &*self.bar.read().unwrap() {
+            bar.inc(steps);
+        }
+    }
+
+    fn finish(&self) {
+        if let Some(bar) = self.bar.write().unwrap().take() {
+            bar.finish();
+        }
+    }
+}
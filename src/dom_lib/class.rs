@@ -0,0 +1,39 @@
+/// Checks whether `class_name` is `superclass_name` or inherits from it,
+/// using the reflection database bundled with rbx-dom.
+///
+/// Classes the database does not know about are only equal to themselves.
+pub fn is_a(class_name: &str, superclass_name: &str) -> bool {
+    if class_name == superclass_name {
+        return true;
+    }
+
+    let database = rbx_reflection_database::get();
+    let mut current = database.classes.get(class_name);
+
+    while let Some(class) = current {
+        if class.name == superclass_name {
+            return true;
+        }
+
+        current = class
+            .superclass
+            .as_ref()
+            .and_then(|superclass| database.classes.get(superclass.as_ref()));
+    }
+
+    false
+}
+
+/// Whether instances of this class should be drawn as parts.
+///
+/// Terrain is technically a BasePart, but it has no box of its own to draw.
+pub fn is_drawable_part(class_name: &str) -> bool {
+    class_name != "Terrain" && is_a(class_name, "BasePart")
+}
+
+/// Whether the reflection database knows about a class.
+pub fn is_known(class_name: &str) -> bool {
+    rbx_reflection_database::get()
+        .classes
+        .contains_key(class_name)
+}
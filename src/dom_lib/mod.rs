@@ -0,0 +1,3 @@
+pub mod class;
+pub mod outline;
+pub mod tree;
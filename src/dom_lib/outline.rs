@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+
+use rbx_dom_weak::{Instance, WeakDom};
+use rbx_types::Ref;
+use serde::Serialize;
+
+use super::class;
+use super::tree::get_descendants;
+
+/// An instance and the instances below it, as shown by `inspect`.
+#[derive(Debug, Clone, Serialize)]
+pub struct OutlineNode {
+    pub name: String,
+    pub class: String,
+    /// Names from the root of the file down to this instance, ready to be
+    /// used as the `dir` of a rule
+    pub path: Vec<String>,
+    /// How many drawable parts there are in this instance and everything
+    /// under it, whether or not they are shown
+    pub parts: usize,
+    pub children: Vec<OutlineNode>,
+}
+
+/// Which instances to include in an outline.
+#[derive(Debug, Clone, Default)]
+pub struct OutlineOptions {
+    /// How many levels below the root to go, where 1 is only the root's
+    /// children. Goes all the way down when `None`.
+    pub max_depth: Option<usize>,
+    /// Only include instances of these classes, or classes that inherit from
+    /// them, and the instances leading down to them. Includes everything
+    /// when empty.
+    pub classes: Vec<String>,
+}
+
+/// Outlines everything under `root`.
+pub fn outline(dom: &WeakDom, root: &Instance, options: &OutlineOptions) -> Vec<OutlineNode> {
+    let parts = count_parts(dom, root);
+    outline_children(dom, root, &[], 1, options, &parts)
+}
+
+/// Counts the drawable parts in `root` and every instance under it, in a
+/// single pass from the bottom up.
+///
+/// Everything is counted, including what the outline leaves out.
+fn count_parts(dom: &WeakDom, root: &Instance) -> HashMap<Ref, usize> {
+    let mut drawable: HashMap<&str, bool> = HashMap::new();
+    let mut counts = HashMap::new();
+
+    // descendants are listed parents first, so going backwards reaches
+    // every instance after its children.
+    let root_ref = root.referent();
+    let descendants = get_descendants(dom, &root_ref);
+    for &referent in descendants.iter().rev().chain(Some(&root_ref)) {
+        let instance = dom.get_by_ref(referent).unwrap();
+        let is_part = *drawable
+            .entry(instance.class.as_str())
+            .or_insert_with(|| class::is_drawable_part(&instance.class));
+        let children: usize = instance.children().iter().map(|child| counts[child]).sum();
+        counts.insert(referent, usize::from(is_part) + children);
+    }
+
+    counts
+}
+
+fn outline_children(
+    dom: &WeakDom,
+    parent: &Instance,
+    path: &[String],
+    depth: usize,
+    options: &OutlineOptions,
+    parts: &HashMap<Ref, usize>,
+) -> Vec<OutlineNode> {
+    if let Some(max_depth) = options.max_depth {
+        if depth > max_depth {
+            return Vec::new();
+        }
+    }
+
+    parent
+        .children()
+        .iter()
+        .map(|&child| dom.get_by_ref(child).unwrap())
+        .filter_map(|child| outline_node(dom, child, path, depth, options, parts))
+        .collect()
+}
+
+fn outline_node(
+    dom: &WeakDom,
+    instance: &Instance,
+    parent_path: &[String],
+    depth: usize,
+    options: &OutlineOptions,
+    parts: &HashMap<Ref, usize>,
+) -> Option<OutlineNode> {
+    let mut path = parent_path.to_vec();
+    path.push(instance.name.clone());
+
+    let children = outline_children(dom, instance, &path, depth + 1, options, parts);
+    let wanted = options.classes.is_empty()
+        || options
+            .classes
+            .iter()
+            .any(|class_name| class::is_a(&instance.class, class_name));
+
+    // instances that weren't asked for are still shown when they lead to
+    // ones that were.
+    if !wanted && children.is_empty() {
+        return None;
+    }
+
+    Some(OutlineNode {
+        name: instance.name.clone(),
+        class: instance.class.clone(),
+        path,
+        parts: parts[&instance.referent()],
+        children,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use rbx_dom_weak::InstanceBuilder;
+
+    use super::*;
+
+    fn place() -> WeakDom {
+        let mut dom = WeakDom::new(InstanceBuilder::new("DataModel"));
+        let workspace = dom.insert(dom.root_ref(), InstanceBuilder::new("Workspace"));
+        dom.insert(dom.root_ref(), InstanceBuilder::new("Lighting"));
+
+        let car = dom.insert(workspace, InstanceBuilder::new("Model").with_name("Car"));
+        dom.insert(car, InstanceBuilder::new("Part").with_name("Body"));
+        dom.insert(car, InstanceBuilder::new("WedgePart").with_name("Hood"));
+        dom.insert(car, InstanceBuilder::new("Script"));
+
+        let map = dom.insert(workspace, InstanceBuilder::new("Folder").with_name("Map"));
+        dom.insert(map, InstanceBuilder::new("MeshPart").with_name("Road"));
+        dom
+    }
+
+    fn names(nodes: &[OutlineNode]) -> Vec<&str> {
+        nodes.iter().map(|node| node.name.as_str()).collect()
+    }
+
+    #[test]
+    fn counts_parts_in_every_subtree() {
+        let dom = place();
+        let nodes = outline(&dom, dom.root(), &OutlineOptions::default());
+        assert_eq!(names(&nodes), ["Workspace", "Lighting"]);
+        assert_eq!((nodes[0].parts, nodes[1].parts), (3, 0));
+
+        let workspace = &nodes[0].children;
+        assert_eq!(names(workspace), ["Car", "Map"]);
+        assert_eq!((workspace[0].parts, workspace[1].parts), (2, 1));
+        assert_eq!(workspace[0].children[0].parts, 1);
+        assert_eq!(workspace[0].children[0].path, ["Workspace", "Car", "Body"]);
+    }
+
+    #[test]
+    fn counts_what_depth_cuts_off() {
+        let dom = place();
+        let options = OutlineOptions {
+            max_depth: Some(1),
+            ..OutlineOptions::default()
+        };
+        let nodes = outline(&dom, dom.root(), &options);
+        assert_eq!(names(&nodes), ["Workspace", "Lighting"]);
+        assert!(nodes[0].children.is_empty());
+        assert_eq!(nodes[0].parts, 3);
+    }
+
+    #[test]
+    fn keeps_the_way_to_matching_classes() {
+        let dom = place();
+        let options = OutlineOptions {
+            classes: vec!["MeshPart".to_owned()],
+            ..OutlineOptions::default()
+        };
+        let nodes = outline(&dom, dom.root(), &options);
+        assert_eq!(names(&nodes), ["Workspace"]);
+        assert_eq!(nodes[0].parts, 3);
+        assert_eq!(names(&nodes[0].children), ["Map"]);
+        assert_eq!(names(&nodes[0].children[0].children), ["Road"]);
+    }
+}
@@ -0,0 +1,55 @@
+use std::collections::VecDeque;
+
+use rbx_dom_weak::{Instance, WeakDom};
+use rbx_types::Ref;
+
+/// Collects every descendant of an instance, depth first, in the same
+/// order Studio's explorer would list them.
+pub fn get_descendants(dom: &WeakDom, inst_ref: &Ref) -> Vec<Ref> {
+    let instance = dom
+        .get_by_ref(*inst_ref)
+        .expect("received invalid child in tree when recursing through descendants");
+
+    let mut descendants: Vec<Ref> = Vec::new();
+    let mut stack = VecDeque::from_iter(instance.children().iter());
+
+    while let Some(current) = stack.pop_front() {
+        descendants.push(*current);
+
+        let current_instance = dom
+            .get_by_ref(*current)
+            .expect("received invalid child in tree when recursing through descendants");
+
+        for child in current_instance.children().iter().rev() {
+            stack.push_front(child);
+        }
+    }
+
+    descendants
+}
+
+/// Builds the dot separated path of an instance, leaving out the root.
+pub fn get_full_name(dom: &WeakDom, instance: &Instance) -> String {
+    let mut names = vec![instance.name.as_str()];
+    let mut parent = dom.get_by_ref(instance.parent());
+
+    while let Some(current) = parent {
+        if current.referent() == dom.root_ref() {
+            break;
+        }
+        names.push(current.name.as_str());
+        parent = dom.get_by_ref(current.parent());
+    }
+
+    names.reverse();
+    names.join(".")
+}
+
+/// Finds a direct child of an instance by name.
+pub fn find_child<'a>(dom: &'a WeakDom, instance: &Instance, name: &str) -> Option<&'a Instance> {
+    instance
+        .children()
+        .iter()
+        .map(|&x| dom.get_by_ref(x).unwrap())
+        .find(|x| x.name == name)
+}
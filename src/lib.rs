@@ -1,2 +1,6 @@
 pub mod cli;
+pub mod dom_lib;
 pub mod math_lib;
+pub mod output;
+pub mod render;
+pub mod terrain;
@@ -58,8 +58,8 @@ fn main() {
 
     let log_filter = match options.global.verbosity {
         0 => "info",
-        1 => "info,librojo=debug",
-        2 => "info,librojo=trace",
+        1 => "info,librojo=debug,road_render=debug",
+        2 => "info,librojo=trace,road_render=trace",
         _ => "trace",
     };
 
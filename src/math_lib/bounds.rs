@@ -0,0 +1,146 @@
+use rbx_types::{Matrix3, Vector3};
+
+/// An axis aligned box on the world X/Z plane.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bounds {
+    pub min_x: f32,
+    pub min_z: f32,
+    pub max_x: f32,
+    pub max_z: f32,
+}
+
+impl Bounds {
+    pub fn new(min_x: f32, min_z: f32, max_x: f32, max_z: f32) -> Bounds {
+        Bounds {
+            min_x,
+            min_z,
+            max_x,
+            max_z,
+        }
+    }
+
+    /// Computes the top down footprint of a box with the given position,
+    /// size and rotation.
+    pub fn from_part(pos: Vector3, size: Vector3, rot: Matrix3) -> Bounds {
+        // rows of the rotation matrix, so the world X extent of the box is
+        // the sum of each rotated half size along X.
+        let half_x =
+            (rot.x.x.abs() * size.x + rot.x.y.abs() * size.y + rot.x.z.abs() * size.z) * 0.5f32;
+        let half_z =
+            (rot.z.x.abs() * size.x + rot.z.y.abs() * size.y + rot.z.z.abs() * size.z) * 0.5f32;
+
+        Bounds::new(
+            pos.x - half_x,
+            pos.z - half_z,
+            pos.x + half_x,
+            pos.z + half_z,
+        )
+    }
+
+    pub fn union(&self, other: &Bounds) -> Bounds {
+        Bounds::new(
+            self.min_x.min(other.min_x),
+            self.min_z.min(other.min_z),
+            self.max_x.max(other.max_x),
+            self.max_z.max(other.max_z),
+        )
+    }
+
+    /// Grows the box by `amount` on every side.
+    pub fn expand(&self, amount: f32) -> Bounds {
+        Bounds::new(
+            self.min_x - amount,
+            self.min_z - amount,
+            self.max_x + amount,
+            self.max_z + amount,
+        )
+    }
+
+    /// Whether the two boxes overlap, touching edges included.
+    pub fn intersects(&self, other: &Bounds) -> bool {
+        self.min_x <= other.max_x
+            && other.min_x <= self.max_x
+            && self.min_z <= other.max_z
+            && other.min_z <= self.max_z
+    }
+
+    pub fn width(&self) -> f32 {
+        self.max_x - self.min_x
+    }
+
+    pub fn depth(&self) -> f32 {
+        self.max_z - self.min_z
+    }
+
+    pub fn center(&self) -> (f32, f32) {
+        (
+            (self.min_x + self.max_x) * 0.5f32,
+            (self.min_z + self.max_z) * 0.5f32,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math_lib::rotations::{pitch_30, quarter_turn};
+
+    fn assert_close(actual: Bounds, expected: Bounds) {
+        let pairs = [
+            (actual.min_x, expected.min_x),
+            (actual.min_z, expected.min_z),
+            (actual.max_x, expected.max_x),
+            (actual.max_z, expected.max_z),
+        ];
+        assert!(
+            pairs.iter().all(|(a, b)| (a - b).abs() < 1e-4),
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn unrotated_parts() {
+        let bounds = Bounds::from_part(
+            Vector3::new(1f32, 2f32, 3f32),
+            Vector3::new(2f32, 4f32, 6f32),
+            Matrix3::identity(),
+        );
+        assert_close(bounds, Bounds::new(0f32, 0f32, 2f32, 6f32));
+    }
+
+    #[test]
+    fn turned_parts() {
+        let size = Vector3::new(2f32, 4f32, 6f32);
+
+        // a quarter turn around Y swaps the X and Z extents.
+        let bounds = Bounds::from_part(Vector3::new(1f32, 2f32, 3f32), size, quarter_turn());
+        assert_close(bounds, Bounds::new(-2f32, 2f32, 4f32, 4f32));
+
+        // an eighth turn puts both corners of a 2x2 square on each axis.
+        let (c, s) = (0.5f32.sqrt(), 0.5f32.sqrt());
+        let eighth = Matrix3::new(
+            Vector3::new(c, 0f32, s),
+            Vector3::new(0f32, 1f32, 0f32),
+            Vector3::new(-s, 0f32, c),
+        );
+        let square = Vector3::new(2f32, 1f32, 2f32);
+        let bounds = Bounds::from_part(Vector3::new(0f32, 0f32, 0f32), square, eighth);
+        let half = 2f32.sqrt();
+        assert_close(bounds, Bounds::new(-half, -half, half, half));
+    }
+
+    #[test]
+    fn pitched_parts() {
+        // its height now reaches along Z too.
+        let bounds = Bounds::from_part(
+            Vector3::new(0f32, 0f32, 0f32),
+            Vector3::new(4f32, 2f32, 4f32),
+            pitch_30(),
+        );
+        // (2 * sin + 4 * cos) / 2
+        let half_z = 0.5f32 + 3f32.sqrt();
+        assert_close(bounds, Bounds::new(-2f32, -half_z, 2f32, half_z));
+    }
+}
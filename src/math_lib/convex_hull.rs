@@ -0,0 +1,83 @@
+/// Computes the convex hull of a set of 2D points using the monotone chain
+/// algorithm.
+///
+/// The hull is returned in counter clockwise order without repeating the
+/// first point. Fewer than three distinct points are returned as-is.
+pub fn convex_hull(points: &[(f32, f32)]) -> Vec<(f32, f32)> {
+    let mut sorted = points.to_vec();
+    sorted.sort_by(|a, b| {
+        a.0.partial_cmp(&b.0)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then(a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+    });
+    sorted.dedup();
+
+    if sorted.len() < 3 {
+        return sorted;
+    }
+
+    let mut lower: Vec<(f32, f32)> = Vec::with_capacity(sorted.len());
+    for &point in sorted.iter() {
+        while lower.len() >= 2
+            && cross(lower[lower.len() - 2], lower[lower.len() - 1], point) <= 0f32
+        {
+            lower.pop();
+        }
+        lower.push(point);
+    }
+
+    let mut upper: Vec<(f32, f32)> = Vec::with_capacity(sorted.len());
+    for &point in sorted.iter().rev() {
+        while upper.len() >= 2
+            && cross(upper[upper.len() - 2], upper[upper.len() - 1], point) <= 0f32
+        {
+            upper.pop();
+        }
+        upper.push(point);
+    }
+
+    // the last point of each half is the first point of the other.
+    lower.pop();
+    upper.pop();
+    lower.extend(upper);
+    lower
+}
+
+fn cross(o: (f32, f32), a: (f32, f32), b: (f32, f32)) -> f32 {
+    (a.0 - o.0) * (b.1 - o.1) - (a.1 - o.1) * (b.0 - o.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drops_inner_and_edge_points() {
+        let points = [
+            (0f32, 0f32),
+            (2f32, 2f32),
+            (1f32, 0f32),
+            (1f32, 1f32),
+            (0f32, 2f32),
+            (2f32, 0f32),
+        ];
+        assert_eq!(
+            convex_hull(&points),
+            vec![(0f32, 0f32), (2f32, 0f32), (2f32, 2f32), (0f32, 2f32)]
+        );
+    }
+
+    #[test]
+    fn degenerate_inputs() {
+        assert_eq!(convex_hull(&[]), vec![]);
+        assert_eq!(
+            convex_hull(&[(1f32, 1f32), (1f32, 1f32), (0f32, 0f32)]),
+            vec![(0f32, 0f32), (1f32, 1f32)]
+        );
+        // points on a line only keep its ends.
+        assert_eq!(
+            convex_hull(&[(2f32, 2f32), (0f32, 0f32), (1f32, 1f32)]),
+            vec![(0f32, 0f32), (2f32, 2f32)]
+        );
+    }
+}
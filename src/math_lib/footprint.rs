@@ -0,0 +1,302 @@
+use std::f32::consts::TAU;
+
+use rbx_types::{Matrix3, Vector3};
+
+use super::convex_hull::convex_hull;
+
+/// How many points are used to approximate round outlines.
+const CIRCLE_SEGMENTS: usize = 32;
+
+/// The solid shapes a part can take.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartShape {
+    Block,
+    Ball,
+    Cylinder,
+    Wedge,
+    CornerWedge,
+}
+
+impl PartShape {
+    /// Maps a value of the `PartType` enum, as stored in `Part.Shape`.
+    pub fn from_part_type(value: u32) -> PartShape {
+        match value {
+            0 => PartShape::Ball,
+            2 => PartShape::Cylinder,
+            3 => PartShape::Wedge,
+            4 => PartShape::CornerWedge,
+            _ => PartShape::Block,
+        }
+    }
+}
+
+/// Computes the top down outline of a part as a polygon on the world X/Z
+/// plane.
+pub fn footprint(shape: PartShape, pos: Vector3, size: Vector3, rot: Matrix3) -> Vec<(f32, f32)> {
+    let half = Vector3::new(size.x * 0.5f32, size.y * 0.5f32, size.z * 0.5f32);
+
+    let local_points: Vec<Vector3> = match shape {
+        PartShape::Block => vec![
+            Vector3::new(half.x, half.y, half.z),
+            Vector3::new(half.x, half.y, -half.z),
+            Vector3::new(half.x, -half.y, half.z),
+            Vector3::new(half.x, -half.y, -half.z),
+            Vector3::new(-half.x, half.y, half.z),
+            Vector3::new(-half.x, half.y, -half.z),
+            Vector3::new(-half.x, -half.y, half.z),
+            Vector3::new(-half.x, -half.y, -half.z),
+        ],
+        // balls are always as wide as their smallest axis and look the same
+        // from every angle.
+        PartShape::Ball => {
+            let radius = half.x.min(half.y).min(half.z);
+            return circle_points(radius)
+                .into_iter()
+                .map(|(x, z)| (pos.x + x, pos.z + z))
+                .collect();
+        }
+        // cylinders run along their X axis, so sample both end caps and let
+        // the hull join them up.
+        PartShape::Cylinder => {
+            let radius = half.y.min(half.z);
+            let mut points = Vec::with_capacity(CIRCLE_SEGMENTS * 2);
+            for (y, z) in circle_points(radius) {
+                points.push(Vector3::new(half.x, y, z));
+                points.push(Vector3::new(-half.x, y, z));
+            }
+            points
+        }
+        // the slope runs from the bottom front edge up to the top back edge.
+        PartShape::Wedge => vec![
+            Vector3::new(half.x, -half.y, half.z),
+            Vector3::new(half.x, -half.y, -half.z),
+            Vector3::new(-half.x, -half.y, half.z),
+            Vector3::new(-half.x, -half.y, -half.z),
+            Vector3::new(half.x, half.y, half.z),
+            Vector3::new(-half.x, half.y, half.z),
+        ],
+        // a full base with a single raised corner.
+        PartShape::CornerWedge => vec![
+            Vector3::new(half.x, -half.y, half.z),
+            Vector3::new(half.x, -half.y, -half.z),
+            Vector3::new(-half.x, -half.y, half.z),
+            Vector3::new(-half.x, -half.y, -half.z),
+            Vector3::new(half.x, half.y, -half.z),
+        ],
+    };
+
+    let projected: Vec<(f32, f32)> = local_points
+        .iter()
+        .map(|local| project_to_xz(pos, rot, *local))
+        .collect();
+
+    convex_hull(&projected)
+}
+
+/// Finds the direction the upper-most face of a part looks in, as a unit
+/// vector in world space.
+///
+/// Round parts are treated like the box around them.
+pub fn top_normal(shape: PartShape, size: Vector3, rot: Matrix3) -> Vector3 {
+    let half = Vector3::new(size.x * 0.5f32, size.y * 0.5f32, size.z * 0.5f32);
+    let x = Vector3::new(1f32, 0f32, 0f32);
+    let y = Vector3::new(0f32, 1f32, 0f32);
+    let z = Vector3::new(0f32, 0f32, 1f32);
+
+    // the outward normal of every face in part space, sloped faces
+    // included.
+    let faces: Vec<Vector3> = match shape {
+        PartShape::Wedge => vec![
+            Vector3::new(0f32, half.z, -half.y),
+            negate(y),
+            x,
+            negate(x),
+            z,
+        ],
+        PartShape::CornerWedge => vec![
+            Vector3::new(-half.y, half.x, 0f32),
+            Vector3::new(0f32, half.z, half.y),
+            negate(y),
+            x,
+            negate(z),
+        ],
+        _ => vec![x, negate(x), y, negate(y), z, negate(z)],
+    };
+
+    faces
+        .into_iter()
+        .map(|local| {
+            normalize(Vector3::new(
+                rot.x.x * local.x + rot.x.y * local.y + rot.x.z * local.z,
+                rot.y.x * local.x + rot.y.y * local.y + rot.y.z * local.z,
+                rot.z.x * local.x + rot.z.y * local.y + rot.z.z * local.z,
+            ))
+        })
+        .fold(
+            negate(y),
+            |top, normal| {
+                if normal.y > top.y {
+                    normal
+                } else {
+                    top
+                }
+            },
+        )
+}
+
+fn negate(v: Vector3) -> Vector3 {
+    Vector3::new(-v.x, -v.y, -v.z)
+}
+
+fn normalize(v: Vector3) -> Vector3 {
+    let length = (v.x * v.x + v.y * v.y + v.z * v.z).sqrt();
+    if length <= f32::EPSILON {
+        return Vector3::new(0f32, 1f32, 0f32);
+    }
+
+    Vector3::new(v.x / length, v.y / length, v.z / length)
+}
+
+/// Moves a point from part space into world space and drops its height.
+fn project_to_xz(pos: Vector3, rot: Matrix3, local: Vector3) -> (f32, f32) {
+    (
+        pos.x + rot.x.x * local.x + rot.x.y * local.y + rot.x.z * local.z,
+        pos.z + rot.z.x * local.x + rot.z.y * local.y + rot.z.z * local.z,
+    )
+}
+
+fn circle_points(radius: f32) -> Vec<(f32, f32)> {
+    (0..CIRCLE_SEGMENTS)
+        .map(|i| {
+            let angle = i as f32 / CIRCLE_SEGMENTS as f32 * TAU;
+            (angle.cos() * radius, angle.sin() * radius)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math_lib::rotations::{pitch_30, quarter_turn};
+
+    fn assert_points_close(actual: &[(f32, f32)], expected: &[(f32, f32)]) {
+        assert_eq!(actual.len(), expected.len(), "{:?}", actual);
+        for (a, b) in actual.iter().zip(expected) {
+            assert!(
+                (a.0 - b.0).abs() < 1e-4 && (a.1 - b.1).abs() < 1e-4,
+                "{:?} != {:?}",
+                actual,
+                expected
+            );
+        }
+    }
+
+    fn assert_vector_close(actual: Vector3, expected: Vector3) {
+        assert!(
+            (actual.x - expected.x).abs() < 1e-4
+                && (actual.y - expected.y).abs() < 1e-4
+                && (actual.z - expected.z).abs() < 1e-4,
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn block_outlines() {
+        let outline = footprint(
+            PartShape::Block,
+            Vector3::new(10f32, 0f32, 20f32),
+            Vector3::new(2f32, 1f32, 4f32),
+            Matrix3::identity(),
+        );
+        assert_points_close(
+            &outline,
+            &[(9f32, 18f32), (11f32, 18f32), (11f32, 22f32), (9f32, 22f32)],
+        );
+    }
+
+    #[test]
+    fn wedge_outlines_turn_with_the_part() {
+        let size = Vector3::new(2f32, 2f32, 8f32);
+        let origin = Vector3::new(0f32, 0f32, 0f32);
+
+        let outline = footprint(PartShape::Wedge, origin, size, Matrix3::identity());
+        assert_points_close(
+            &outline,
+            &[(-1f32, -4f32), (1f32, -4f32), (1f32, 4f32), (-1f32, 4f32)],
+        );
+
+        let outline = footprint(PartShape::Wedge, origin, size, quarter_turn());
+        assert_points_close(
+            &outline,
+            &[(-4f32, -1f32), (4f32, -1f32), (4f32, 1f32), (-4f32, 1f32)],
+        );
+    }
+
+    #[test]
+    fn round_outlines() {
+        let center = Vector3::new(5f32, 0f32, -5f32);
+
+        // balls use their smallest axis.
+        let outline = footprint(
+            PartShape::Ball,
+            center,
+            Vector3::new(4f32, 6f32, 8f32),
+            Matrix3::identity(),
+        );
+        assert_eq!(outline.len(), CIRCLE_SEGMENTS);
+        for (x, z) in outline {
+            let distance = ((x - center.x).powi(2) + (z - center.z).powi(2)).sqrt();
+            assert!((distance - 2f32).abs() < 1e-4);
+        }
+
+        // lying along X, a cylinder is seen as a rectangle from above.
+        let outline = footprint(
+            PartShape::Cylinder,
+            center,
+            Vector3::new(6f32, 2f32, 2f32),
+            Matrix3::identity(),
+        );
+        assert_points_close(
+            &outline,
+            &[(2f32, -6f32), (8f32, -6f32), (8f32, -4f32), (2f32, -4f32)],
+        );
+    }
+
+    #[test]
+    fn top_normals() {
+        let up = Vector3::new(0f32, 1f32, 0f32);
+        let cube = Vector3::new(2f32, 2f32, 2f32);
+        assert_vector_close(top_normal(PartShape::Block, cube, Matrix3::identity()), up);
+        assert_vector_close(top_normal(PartShape::Ball, cube, quarter_turn()), up);
+
+        // the slope rises 4 studs over 8, so its normal leans back by
+        // (0, 8, -4) normalized.
+        let wedge = Vector3::new(2f32, 4f32, 8f32);
+        let (rise, lean) = (2f32 / 5f32.sqrt(), 1f32 / 5f32.sqrt());
+        assert_vector_close(
+            top_normal(PartShape::Wedge, wedge, Matrix3::identity()),
+            Vector3::new(0f32, rise, -lean),
+        );
+        assert_vector_close(
+            top_normal(PartShape::Wedge, wedge, quarter_turn()),
+            Vector3::new(-lean, rise, 0f32),
+        );
+
+        // of the two slopes, the one across the long side is flatter.
+        assert_vector_close(
+            top_normal(
+                PartShape::CornerWedge,
+                Vector3::new(4f32, 4f32, 8f32),
+                Matrix3::identity(),
+            ),
+            Vector3::new(0f32, rise, lean),
+        );
+
+        assert_vector_close(
+            top_normal(PartShape::Block, cube, pitch_30()),
+            Vector3::new(0f32, 0.75f32.sqrt(), 0.5f32),
+        );
+    }
+}
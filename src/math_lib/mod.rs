@@ -1 +1,31 @@
 pub mod axis_angle_conversion;
+pub mod bounds;
+pub mod convex_hull;
+pub mod footprint;
+pub mod surface;
+
+/// Rotations with hand checked values, shared by the tests of the shapes.
+#[cfg(test)]
+pub(crate) mod rotations {
+    use rbx_types::{Matrix3, Vector3};
+
+    /// A quarter turn around Y, taking part X to world -Z and part Z to
+    /// world X.
+    pub fn quarter_turn() -> Matrix3 {
+        Matrix3::new(
+            Vector3::new(0f32, 0f32, 1f32),
+            Vector3::new(0f32, 1f32, 0f32),
+            Vector3::new(-1f32, 0f32, 0f32),
+        )
+    }
+
+    /// Tipped 30 degrees around X, so part Y leans toward world Z.
+    pub fn pitch_30() -> Matrix3 {
+        let (c, s) = (0.75f32.sqrt(), 0.5f32);
+        Matrix3::new(
+            Vector3::new(1f32, 0f32, 0f32),
+            Vector3::new(0f32, c, -s),
+            Vector3::new(0f32, s, c),
+        )
+    }
+}
@@ -0,0 +1,247 @@
+use rbx_types::{Matrix3, Vector3};
+
+use super::footprint::PartShape;
+
+/// Finds the height of the highest point of a part directly above or below
+/// the world position `x`, `z`, or `None` if the part doesn't cover it.
+///
+/// Works by following a vertical line through the part in part space, where
+/// every shape is either a set of flat faces or a sphere or cylinder.
+pub fn top_at(
+    shape: PartShape,
+    pos: Vector3,
+    size: Vector3,
+    rot: Matrix3,
+    x: f32,
+    z: f32,
+) -> Option<f32> {
+    let half = Vector3::new(size.x * 0.5f32, size.y * 0.5f32, size.z * 0.5f32);
+
+    // the line is `origin + t * direction` in part space, where `t` is the
+    // height relative to the part's position.
+    let (dx, dz) = (x - pos.x, z - pos.z);
+    let origin = Vector3::new(
+        rot.x.x * dx + rot.z.x * dz,
+        rot.x.y * dx + rot.z.y * dz,
+        rot.x.z * dx + rot.z.z * dz,
+    );
+    let direction = Vector3::new(rot.y.x, rot.y.y, rot.y.z);
+
+    let mut span = Span::default();
+    match shape {
+        PartShape::Ball => {
+            let radius = half.x.min(half.y).min(half.z);
+            let along = dot(origin, direction);
+            let gap = dot(origin, origin) - along * along;
+            if gap > radius * radius {
+                return None;
+            }
+            return Some(pos.y - along + (radius * radius - gap).sqrt());
+        }
+        PartShape::Cylinder => {
+            span.clip_slab(origin.x, direction.x, half.x);
+            span.clip_circle(
+                (origin.y, origin.z),
+                (direction.y, direction.z),
+                half.y.min(half.z),
+            );
+        }
+        PartShape::Block | PartShape::Wedge | PartShape::CornerWedge => {
+            span.clip_slab(origin.x, direction.x, half.x);
+            span.clip_slab(origin.y, direction.y, half.y);
+            span.clip_slab(origin.z, direction.z, half.z);
+
+            // the sloped faces all pass through the center of the part.
+            let slopes: &[Vector3] = match shape {
+                PartShape::Wedge => &[Vector3::new(0f32, half.z, -half.y)],
+                PartShape::CornerWedge => &[
+                    Vector3::new(-half.y, half.x, 0f32),
+                    Vector3::new(0f32, half.z, half.y),
+                ],
+                _ => &[],
+            };
+            for normal in slopes {
+                span.clip_plane(dot(*normal, origin), dot(*normal, direction), 0f32);
+            }
+        }
+    }
+
+    span.top().map(|top| pos.y + top)
+}
+
+fn dot(a: Vector3, b: Vector3) -> f32 {
+    a.x * b.x + a.y * b.y + a.z * b.z
+}
+
+/// The range of `t` a line spends inside a shape, narrowed one face at a
+/// time.
+struct Span {
+    low: f32,
+    high: f32,
+}
+
+impl Default for Span {
+    fn default() -> Self {
+        Span {
+            low: f32::NEG_INFINITY,
+            high: f32::INFINITY,
+        }
+    }
+}
+
+impl Span {
+    /// Keeps the part of the line where `offset + t * slope <= limit`.
+    fn clip_plane(&mut self, offset: f32, slope: f32, limit: f32) {
+        if slope.abs() <= f32::EPSILON {
+            if offset > limit {
+                self.high = f32::NEG_INFINITY;
+            }
+        } else if slope > 0f32 {
+            self.high = self.high.min((limit - offset) / slope);
+        } else {
+            self.low = self.low.max((limit - offset) / slope);
+        }
+    }
+
+    /// Keeps the part of the line between `-half` and `half` along one axis.
+    fn clip_slab(&mut self, offset: f32, slope: f32, half: f32) {
+        self.clip_plane(offset, slope, half);
+        self.clip_plane(-offset, -slope, half);
+    }
+
+    /// Keeps the part of the line within `radius` of an axis, given the
+    /// line's position and direction across that axis.
+    fn clip_circle(&mut self, offset: (f32, f32), slope: (f32, f32), radius: f32) {
+        let a = slope.0 * slope.0 + slope.1 * slope.1;
+        let b = offset.0 * slope.0 + offset.1 * slope.1;
+        let c = offset.0 * offset.0 + offset.1 * offset.1 - radius * radius;
+
+        if a <= f32::EPSILON {
+            // running along the axis, so either always or never inside.
+            if c > 0f32 {
+                self.high = f32::NEG_INFINITY;
+            }
+            return;
+        }
+
+        let discriminant = b * b - a * c;
+        if discriminant < 0f32 {
+            self.high = f32::NEG_INFINITY;
+            return;
+        }
+
+        let root = discriminant.sqrt();
+        self.low = self.low.max((-b - root) / a);
+        self.high = self.high.min((-b + root) / a);
+    }
+
+    fn top(&self) -> Option<f32> {
+        if self.low <= self.high && self.high.is_finite() {
+            Some(self.high)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math_lib::rotations::{pitch_30, quarter_turn};
+
+    fn assert_top(actual: Option<f32>, expected: Option<f32>) {
+        match (actual, expected) {
+            (Some(a), Some(b)) if (a - b).abs() < 1e-4 => {}
+            (None, None) => {}
+            _ => panic!("{:?} != {:?}", actual, expected),
+        }
+    }
+
+    #[test]
+    fn wedges_slope_up_toward_back() {
+        // 4 studs tall over 8 studs deep, so the top rises half a stud per
+        // stud along Z.
+        let pos = Vector3::new(10f32, 5f32, 20f32);
+        let size = Vector3::new(2f32, 4f32, 8f32);
+        let top = |rot, x, z| top_at(PartShape::Wedge, pos, size, rot, x, z);
+
+        assert_top(top(Matrix3::identity(), 10f32, 20f32), Some(5f32));
+        assert_top(top(Matrix3::identity(), 10f32, 22f32), Some(6f32));
+        assert_top(top(Matrix3::identity(), 10.5f32, 16f32), Some(3f32));
+        assert_top(top(Matrix3::identity(), 11.5f32, 20f32), None);
+
+        // turned, the back of the wedge faces world X.
+        assert_top(top(quarter_turn(), 12f32, 20.5f32), Some(6f32));
+        assert_top(top(quarter_turn(), 6f32, 20f32), Some(3f32));
+        assert_top(top(quarter_turn(), 10f32, 21.5f32), None);
+    }
+
+    #[test]
+    fn corner_wedges_peak_at_one_corner() {
+        let top = |x, z| {
+            top_at(
+                PartShape::CornerWedge,
+                Vector3::new(0f32, 0f32, 0f32),
+                Vector3::new(4f32, 4f32, 4f32),
+                Matrix3::identity(),
+                x,
+                z,
+            )
+        };
+
+        // the lower of y = x and y = -z.
+        assert_top(top(2f32, -2f32), Some(2f32));
+        assert_top(top(1f32, -1f32), Some(1f32));
+        assert_top(top(1f32, 0f32), Some(0f32));
+        assert_top(top(-1f32, 1f32), Some(-1f32));
+    }
+
+    #[test]
+    fn pitched_blocks() {
+        let top = |z| {
+            top_at(
+                PartShape::Block,
+                Vector3::new(0f32, 0f32, 0f32),
+                Vector3::new(4f32, 2f32, 4f32),
+                pitch_30(),
+                0f32,
+                z,
+            )
+        };
+
+        // the top face is crossed at half the height over cos 30.
+        assert_top(top(0f32), Some(1f32 / 0.75f32.sqrt()));
+        // the footprint reaches 0.5 + sqrt(3) along Z.
+        assert_top(top(2.3f32), None);
+    }
+
+    #[test]
+    fn round_parts() {
+        let center = Vector3::new(0f32, 10f32, 0f32);
+
+        // a sphere of radius 2 is 1.6 tall at 1.2 from its center.
+        let ball = |x| {
+            let size = Vector3::new(4f32, 4f32, 4f32);
+            top_at(PartShape::Ball, center, size, Matrix3::identity(), x, 0f32)
+        };
+        assert_top(ball(0f32), Some(12f32));
+        assert_top(ball(1.2f32), Some(11.6f32));
+        assert_top(ball(2.1f32), None);
+
+        // lying along X, the cylinder is round across Z.
+        let size = Vector3::new(6f32, 4f32, 4f32);
+        let lying = |x, z| top_at(PartShape::Cylinder, center, size, Matrix3::identity(), x, z);
+        assert_top(lying(2f32, 1.2f32), Some(11.6f32));
+        assert_top(lying(3.5f32, 0f32), None);
+
+        // stood on its end, the top is the flat cap.
+        let standing = Matrix3::new(
+            Vector3::new(0f32, -1f32, 0f32),
+            Vector3::new(1f32, 0f32, 0f32),
+            Vector3::new(0f32, 0f32, 1f32),
+        );
+        let stood = |z| top_at(PartShape::Cylinder, center, size, standing, 0f32, z);
+        assert_top(stood(1.9f32), Some(13f32));
+        assert_top(stood(2.1f32), None);
+    }
+}
@@ -0,0 +1,184 @@
+use std::fs::File;
+use std::io::{BufWriter, Seek, Write};
+use std::path::Path;
+
+use anyhow::{bail, Context};
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::webp::WebPEncoder;
+#[cfg(feature = "webp-encoder")]
+use image::codecs::webp::WebPQuality;
+use image::{ColorType, DynamicImage, ImageBuffer, ImageEncoder, ImageFormat, Luma, RgbaImage};
+use tiny_skia::{ColorU8, Pixmap};
+
+const UNKNOWN_IMAGE_FORMAT_ERROR: &str = "Could not detect what kind of image to write. \
+										Expected output to end in .png, .jpg, .jpeg, .webp, .bmp, .tif or .tiff.";
+
+/// The formats that `quality` applies to, for messages.
+#[cfg(feature = "webp-encoder")]
+pub const QUALITY_FORMATS: &str = "JPEG and WebP";
+#[cfg(not(feature = "webp-encoder"))]
+pub const QUALITY_FORMATS: &str = "JPEG";
+
+/// Converts a pixmap into a regular, non-premultiplied RGBA image.
+pub fn pixmap_to_image(pixmap: &Pixmap) -> RgbaImage {
+    let mut data = Vec::with_capacity(pixmap.data().len());
+    for pixel in pixmap.pixels() {
+        let color = pixel.demultiply();
+        data.extend_from_slice(&[color.red(), color.green(), color.blue(), color.alpha()]);
+    }
+
+    RgbaImage::from_raw(pixmap.width(), pixmap.height(), data)
+        .expect("pixmap data did not match its size")
+}
+
+/// Converts a regular RGBA image into a pixmap. Returns `None` for empty
+/// images.
+pub fn image_to_pixmap(image: &RgbaImage) -> Option<Pixmap> {
+    let mut pixmap = Pixmap::new(image.width(), image.height())?;
+    for (pixel, color) in pixmap.pixels_mut().iter_mut().zip(image.pixels()) {
+        *pixel = ColorU8::from_rgba(color[0], color[1], color[2], color[3]).premultiply();
+    }
+
+    Some(pixmap)
+}
+
+/// Writes a pixmap to `path`, picking the image format from its extension.
+///
+/// `quality` ranges from 1 to 100 and is only used by lossy formats, see
+/// [`uses_quality`].
+pub fn save_pixmap(pixmap: &Pixmap, path: &Path, quality: u8) -> anyhow::Result<()> {
+    save_image(pixmap_to_image(pixmap), path, quality)
+}
+
+/// Whether the image written to `path` is lossy, and so uses the quality
+/// passed to [`save_image`].
+///
+/// WebP is only lossy when built with the `webp-encoder` feature, which
+/// needs libwebp. Otherwise it is written losslessly.
+pub fn uses_quality(path: &Path) -> bool {
+    match ImageFormat::from_path(path) {
+        Ok(ImageFormat::WebP) => cfg!(feature = "webp-encoder"),
+        format => matches!(format, Ok(ImageFormat::Jpeg)),
+    }
+}
+
+/// Picks the format to write `path` in from its extension, failing for
+/// anything that can't be written.
+pub fn image_format(path: &Path) -> anyhow::Result<ImageFormat> {
+    match ImageFormat::from_path(path) {
+        Ok(
+            format @ (ImageFormat::Png
+            | ImageFormat::Jpeg
+            | ImageFormat::WebP
+            | ImageFormat::Bmp
+            | ImageFormat::Tiff),
+        ) => Ok(format),
+        _ => bail!(UNKNOWN_IMAGE_FORMAT_ERROR),
+    }
+}
+
+/// Fails unless `quality` is between 1 and 100.
+pub fn check_quality(quality: u8) -> anyhow::Result<()> {
+    if !(1..=100).contains(&quality) {
+        bail!("Quality must be between 1 and 100, got {}.", quality);
+    }
+
+    Ok(())
+}
+
+/// Writes an image to `path`, picking the image format from its extension.
+///
+/// `quality` ranges from 1 to 100 and is only used by lossy formats, see
+/// [`uses_quality`].
+pub fn save_image(image: RgbaImage, path: &Path, quality: u8) -> anyhow::Result<()> {
+    let format = image_format(path)?;
+    check_quality(quality)?;
+
+    let write = || -> anyhow::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        write_image(image, format, &mut writer, quality)?;
+        writer.flush()?;
+        Ok(())
+    };
+
+    write().with_context(|| format!("Could not write image to {}", path.display()))
+}
+
+/// Encodes a pixmap as `format` into `writer`, see [`write_image`].
+pub fn write_pixmap<W: Write + Seek>(
+    pixmap: &Pixmap,
+    format: ImageFormat,
+    writer: &mut W,
+    quality: u8,
+) -> anyhow::Result<()> {
+    write_image(pixmap_to_image(pixmap), format, writer, quality)
+}
+
+/// Encodes an image as `format` into `writer`.
+///
+/// `quality` ranges from 1 to 100 and is only used by lossy formats, see
+/// [`uses_quality`].
+pub fn write_image<W: Write + Seek>(
+    image: RgbaImage,
+    format: ImageFormat,
+    writer: &mut W,
+    quality: u8,
+) -> anyhow::Result<()> {
+    check_quality(quality)?;
+    let (width, height) = image.dimensions();
+
+    match format {
+        ImageFormat::Png | ImageFormat::Bmp | ImageFormat::Tiff => {
+            image.write_to(writer, format)?;
+        }
+        ImageFormat::Jpeg => {
+            // jpeg has no alpha channel.
+            let rgb = DynamicImage::ImageRgba8(image).into_rgb8();
+            JpegEncoder::new_with_quality(writer, quality).write_image(
+                &rgb,
+                width,
+                height,
+                ColorType::Rgb8,
+            )?;
+        }
+        ImageFormat::WebP => {
+            // image has deprecated libwebp in favour of its own encoder,
+            // which is lossless only.
+            #[cfg(feature = "webp-encoder")]
+            #[allow(deprecated)]
+            let encoder = WebPEncoder::new_with_quality(writer, WebPQuality::lossy(quality));
+            #[cfg(not(feature = "webp-encoder"))]
+            let encoder = WebPEncoder::new_lossless(writer);
+            encoder.write_image(&image, width, height, ColorType::Rgba8)?;
+        }
+        _ => bail!(UNKNOWN_IMAGE_FORMAT_ERROR),
+    }
+
+    Ok(())
+}
+
+/// Picks the format to write a heightmap at `path` in. Only PNG and TIFF
+/// can hold 16 bit grayscale.
+pub fn heightmap_format(path: &Path) -> anyhow::Result<ImageFormat> {
+    match ImageFormat::from_path(path) {
+        Ok(format @ (ImageFormat::Png | ImageFormat::Tiff)) => Ok(format),
+        _ => bail!("Heightmaps can only be written as .png, .tif or .tiff."),
+    }
+}
+
+/// Writes a 16 bit grayscale image, which only PNG and TIFF can hold.
+pub fn save_heightmap(image: &ImageBuffer<Luma<u16>, Vec<u16>>, path: &Path) -> anyhow::Result<()> {
+    let format = heightmap_format(path)?;
+    image
+        .save_with_format(path, format)
+        .with_context(|| format!("Could not write heightmap to {}", path.display()))
+}
+
+/// Encodes a heightmap as `format`, see [`heightmap_format`].
+pub fn write_heightmap<W: Write + Seek>(
+    image: &ImageBuffer<Luma<u16>, Vec<u16>>,
+    format: ImageFormat,
+    writer: &mut W,
+) -> anyhow::Result<()> {
+    Ok(image.write_to(writer, format)?)
+}
@@ -0,0 +1,41 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+
+/// Writes a file next to `path` and then moves it into place, so anything
+/// watching `path` only ever sees the old file or the whole new one.
+///
+/// The temporary file is named after `path` with a leading dot, and is
+/// removed if `write` fails. Errors name `path` rather than the temporary
+/// file.
+pub fn replace_file<F>(path: &Path, write: F) -> anyhow::Result<()>
+where
+    F: FnOnce(&mut BufWriter<File>) -> anyhow::Result<()>,
+{
+    let temporary = temporary_path(path);
+
+    let result = File::create(&temporary)
+        .map_err(anyhow::Error::from)
+        .and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            write(&mut writer)?;
+            writer.flush()?;
+            Ok(())
+        });
+    if let Err(error) = result {
+        let _ = fs::remove_file(&temporary);
+        return Err(error.context(format!("Could not write {}", path.display())));
+    }
+
+    fs::rename(&temporary, path).with_context(|| format!("Could not replace {}", path.display()))
+}
+
+fn temporary_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}", name))
+}
@@ -0,0 +1,73 @@
+use serde::Deserialize;
+
+use super::error::RenderError;
+
+/// A range of heights to render, for picking out a single floor of a
+/// building or one level of an interchange.
+///
+/// Either end can be left open.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+pub struct HeightBand {
+    /// Leave out parts that are entirely below this height in studs
+    #[serde(default)]
+    pub min_y: Option<f32>,
+    /// Leave out parts that are entirely above this height in studs
+    #[serde(default)]
+    pub max_y: Option<f32>,
+}
+
+impl HeightBand {
+    /// Uses the ends of this band that are set, and the ends of `fallback`
+    /// for the rest.
+    pub fn or(&self, fallback: &HeightBand) -> HeightBand {
+        HeightBand {
+            min_y: self.min_y.or(fallback.min_y),
+            max_y: self.max_y.or(fallback.max_y),
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.min_y.is_none() && self.max_y.is_none()
+    }
+
+    /// Whether anything between `bottom` and `top` falls inside the band,
+    /// touching included.
+    pub fn overlaps(&self, bottom: f32, top: f32) -> bool {
+        let above_min = match self.min_y {
+            Some(min_y) => top >= min_y,
+            None => true,
+        };
+        let below_max = match self.max_y {
+            Some(max_y) => bottom <= max_y,
+            None => true,
+        };
+        above_min && below_max
+    }
+
+    /// Whether a single height falls inside the band.
+    pub fn contains(&self, height: f32) -> bool {
+        self.overlaps(height, height)
+    }
+
+    pub(crate) fn validate(&self, what: &str) -> Result<(), RenderError> {
+        for value in self.min_y.iter().chain(self.max_y.iter()) {
+            if !value.is_finite() {
+                return Err(RenderError::InvalidConfig(format!(
+                    "min_y and max_y of {} must be numbers, found {}",
+                    what, value
+                )));
+            }
+        }
+
+        if let (Some(min_y), Some(max_y)) = (self.min_y, self.max_y) {
+            if min_y > max_y {
+                return Err(RenderError::InvalidConfig(format!(
+                    "min_y of {} ({}) is above its max_y ({})",
+                    what, min_y, max_y
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
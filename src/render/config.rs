@@ -0,0 +1,363 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use rbx_dom_weak::{Instance, WeakDom};
+use serde::Deserialize;
+
+use super::band::HeightBand;
+use super::error::RenderError;
+use super::palette::{self, Palette, PaletteEntry, Texture};
+use super::scene;
+use super::selector::Selector;
+use super::shading::HeightShading;
+use super::style::{check_color, ColorAdjustment, PartStyle};
+use super::view::{Framing, ViewParams};
+use crate::dom_lib::class;
+
+/// Describes what to draw, usually read from a JSON config file.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RenderConfig {
+    /// Draw every part in the Workspace with its own color, ignoring
+    /// `world_files`
+    pub draw_everything: bool,
+    pub world_files: Vec<RenderRule>,
+    /// Draw higher parts over lower ones, instead of in the order they
+    /// appear in the file. Only breaks ties between parts on the same layer.
+    #[serde(default)]
+    pub sort_by_height: bool,
+    /// Color parts in draw_everything mode by their Material
+    #[serde(default)]
+    pub use_palette: bool,
+    /// Colors or textures for materials, on top of the built in palette.
+    /// Keyed by material name.
+    #[serde(default)]
+    pub palette: HashMap<String, PaletteEntry>,
+    /// Tint parts by their height, and optionally light them from one side
+    #[serde(default)]
+    pub height_shading: Option<HeightShading>,
+    /// Whether to draw Workspace.Terrain underneath the parts
+    #[serde(default)]
+    pub draw_terrain: bool,
+    /// Overrides for terrain material colors, keyed by material name
+    #[serde(default)]
+    pub terrain_colors: HashMap<String, Vec<u8>>,
+    /// Only draw parts and terrain within this range of heights
+    #[serde(flatten)]
+    pub band: HeightBand,
+    /// Images to render from the same file in one go
+    #[serde(default)]
+    pub views: Vec<RenderView>,
+}
+
+/// A named image rendered as part of a batch, following the rest of the
+/// config unless it says otherwise.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RenderView {
+    pub name: String,
+    /// Where to write the image, relative to the config. The format is
+    /// picked from the extension.
+    pub output: PathBuf,
+    pub width: u32,
+    pub height: u32,
+    /// The X center of the image in world space
+    #[serde(default)]
+    pub center_x: Option<f32>,
+    /// The Z center of the image in world space
+    #[serde(default)]
+    pub center_z: Option<f32>,
+    #[serde(default)]
+    pub scale: Option<f32>,
+    /// Frame everything selected inside the image, instead of using the
+    /// center and scale
+    #[serde(default)]
+    pub fit: bool,
+    /// Empty space in pixels to leave around a fitted view
+    #[serde(default)]
+    pub margin: f32,
+    /// Replaces the config's draw_everything for this view
+    #[serde(default)]
+    pub draw_everything: Option<bool>,
+    /// Replaces the config's rules for this view
+    #[serde(default)]
+    pub world_files: Option<Vec<RenderRule>>,
+    /// Replaces the config's min_y and max_y for this view
+    #[serde(flatten)]
+    pub band: HeightBand,
+}
+
+impl RenderView {
+    /// The size of the image and how the world is placed in it.
+    pub fn params(&self) -> Result<ViewParams, RenderError> {
+        let framing = if self.fit {
+            Framing::Fit {
+                margin: self.margin,
+            }
+        } else {
+            match (self.center_x, self.center_z, self.scale) {
+                (Some(center_x), Some(center_z), Some(scale)) => Framing::Manual {
+                    offset_x: center_x,
+                    offset_z: center_z,
+                    scale,
+                },
+                _ => {
+                    return Err(RenderError::InvalidConfig(format!(
+                        "view {} needs center_x, center_z and scale, or fit",
+                        self.name
+                    )))
+                }
+            }
+        };
+
+        Ok(ViewParams {
+            width: self.width,
+            height: self.height,
+            framing,
+        })
+    }
+}
+
+/// Selects parts under a folder and gives them a color.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RenderRule {
+    /// RGBA color to draw the parts with. Only used for parts without a
+    /// color of their own when `use_part_color` is set.
+    #[serde(default)]
+    pub color: Option<Vec<u8>>,
+    /// Draw each part with its own Color and Transparency
+    #[serde(default)]
+    pub use_part_color: bool,
+    /// Draw parts with the palette entry for their Material when there is
+    /// one, using `color` or `use_part_color` for the rest
+    #[serde(default)]
+    pub use_palette: bool,
+    #[serde(flatten)]
+    pub adjust: ColorAdjustment,
+    /// Folder to search, as a list of names from the root of the file.
+    /// Searches the Workspace, or the whole of a model, when left out.
+    #[serde(default)]
+    pub dir: Vec<String>,
+    /// Only match instances with exactly this name
+    #[serde(default)]
+    pub part_name: Option<String>,
+    /// Only match parts that meet this condition
+    #[serde(default)]
+    pub select: Option<Selector>,
+    /// Parts from rules with a higher layer are drawn over parts from rules
+    /// with a lower one
+    #[serde(default)]
+    pub layer: i32,
+    #[serde(flatten)]
+    pub style: PartStyle,
+    /// Replaces the config's min_y and max_y for this rule
+    #[serde(flatten)]
+    pub band: HeightBand,
+}
+
+impl RenderRule {
+    /// Whether this rule draws `instance`.
+    pub fn matches(&self, dom: &WeakDom, instance: &Instance) -> bool {
+        // names and selectors can match folders and models too, which have
+        // nothing to draw, so only parts are ever matched.
+        if !class::is_drawable_part(&instance.class) {
+            return false;
+        }
+
+        if let Some(part_name) = &self.part_name {
+            if instance.name != *part_name {
+                return false;
+            }
+        }
+
+        match &self.select {
+            Some(selector) => selector.matches(dom, instance),
+            None => true,
+        }
+    }
+
+    /// Works out the color, and texture if any, to draw a matched part with.
+    pub(crate) fn paint_for(
+        &self,
+        dom: &WeakDom,
+        instance: &Instance,
+        palette: &Palette,
+    ) -> Result<(Vec<u8>, Option<Arc<Texture>>), RenderError> {
+        // parts fall back to their own color when the rule doesn't give one.
+        let base_color = || match &self.color {
+            Some(color) if !self.use_part_color => Ok(color.clone()),
+            fallback => match scene::part_color(dom, instance) {
+                Ok(color) => Ok(color),
+                Err(error) => fallback.clone().ok_or(error),
+            },
+        };
+
+        let (color, texture) = if self.use_palette {
+            palette.paint(instance, base_color)?
+        } else {
+            (base_color()?, None)
+        };
+
+        Ok((self.adjust.apply(&color), texture))
+    }
+
+    /// A short name for the rule to use in messages.
+    fn describe(&self, index: usize) -> String {
+        match &self.part_name {
+            Some(part_name) => format!("part_name {}", part_name),
+            None => format!("world_files[{}]", index),
+        }
+    }
+}
+
+impl RenderConfig {
+    /// Reads and validates a config file.
+    pub fn from_path(path: &Path) -> Result<RenderConfig, RenderError> {
+        let config_raw_data =
+            fs::read_to_string(path).map_err(|source| RenderError::ConfigRead {
+                path: path.to_owned(),
+                source,
+            })?;
+
+        let mut config = RenderConfig::from_json(&config_raw_data)?;
+
+        // textures are written relative to the config, not to wherever the
+        // renderer happens to be run from.
+        if let Some(base) = path.parent() {
+            for entry in config.palette.values_mut() {
+                if let PaletteEntry::Texture { texture, .. } = entry {
+                    *texture = base.join(&*texture);
+                }
+            }
+
+            for view in config.views.iter_mut() {
+                view.output = base.join(&view.output);
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Parses and validates a config from JSON.
+    pub fn from_json(source: &str) -> Result<RenderConfig, RenderError> {
+        let config: RenderConfig = serde_json::from_str(source)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// The config to render one of its views with.
+    pub fn for_view(&self, view: &RenderView) -> RenderConfig {
+        RenderConfig {
+            draw_everything: view.draw_everything.unwrap_or(self.draw_everything),
+            world_files: view
+                .world_files
+                .clone()
+                .unwrap_or_else(|| self.world_files.clone()),
+            band: view.band.or(&self.band),
+            views: Vec::new(),
+            ..self.clone()
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), RenderError> {
+        for (index, rule) in self.world_files.iter().enumerate() {
+            match &rule.color {
+                Some(color) => check_color(color, "color", &rule.describe(index))?,
+                None if !rule.use_part_color && !rule.use_palette => {
+                    return Err(RenderError::InvalidConfig(format!(
+                        "{} needs a color, use_part_color or use_palette",
+                        rule.describe(index)
+                    )));
+                }
+                None => {}
+            }
+
+            rule.adjust.validate(&rule.describe(index))?;
+            rule.style.validate(&rule.describe(index))?;
+            rule.band.validate(&rule.describe(index))?;
+
+            match &rule.select {
+                Some(selector) => selector.validate()?,
+                None if rule.part_name.is_none() => {
+                    return Err(RenderError::InvalidConfig(format!(
+                        "{} needs a part_name or a select",
+                        rule.describe(index)
+                    )));
+                }
+                None => {}
+            }
+        }
+
+        palette::validate(&self.palette)?;
+        self.band.validate("the config")?;
+
+        if let Some(shading) = &self.height_shading {
+            shading.validate()?;
+        }
+
+        for (material, color) in self.terrain_colors.iter() {
+            if color.len() != 4 {
+                return Err(RenderError::InvalidConfig(format!(
+                    "terrain color for {} must have 4 values (RGBA), found {}",
+                    material,
+                    color.len()
+                )));
+            }
+        }
+
+        let mut names = HashSet::new();
+        for view in self.views.iter() {
+            if !names.insert(view.name.as_str()) {
+                return Err(RenderError::InvalidConfig(format!(
+                    "there is more than one view named {}",
+                    view.name
+                )));
+            }
+
+            if view.width == 0 || view.height == 0 {
+                return Err(RenderError::InvalidImageSize {
+                    width: view.width,
+                    height: view.height,
+                });
+            }
+
+            view.params()?;
+            view.band.validate(&format!("view {}", view.name))?;
+            self.for_view(view)
+                .validate()
+                .map_err(|error| match error {
+                    RenderError::InvalidConfig(message) => {
+                        RenderError::InvalidConfig(format!("view {}: {}", view.name, message))
+                    }
+                    error => error,
+                })?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rbx_dom_weak::InstanceBuilder;
+
+    use super::*;
+
+    #[test]
+    fn rules_only_match_parts() {
+        let mut dom = WeakDom::new(InstanceBuilder::new("DataModel"));
+        let root = dom.root_ref();
+        let part = dom.insert(root, InstanceBuilder::new("Part").with_name("Base"));
+        let folder = dom.insert(root, InstanceBuilder::new("Folder").with_name("Base"));
+        let model = dom.insert(root, InstanceBuilder::new("Model").with_name("Base"));
+
+        let by_name: RenderRule = serde_json::from_str(r#"{"part_name": "Base"}"#).unwrap();
+        let by_selector: RenderRule =
+            serde_json::from_str(r#"{"select": {"name": "Base"}}"#).unwrap();
+        for rule in [&by_name, &by_selector] {
+            assert!(rule.matches(&dom, dom.get_by_ref(part).unwrap()));
+            assert!(!rule.matches(&dom, dom.get_by_ref(folder).unwrap()));
+            assert!(!rule.matches(&dom, dom.get_by_ref(model).unwrap()));
+        }
+    }
+}
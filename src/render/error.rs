@@ -0,0 +1,170 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use rbx_dom_weak::{Instance, WeakDom};
+use thiserror::Error;
+
+use crate::dom_lib::tree::get_full_name;
+use crate::terrain::smooth_grid::SmoothGridError;
+
+/// Everything that can go wrong while loading and rendering a file.
+#[derive(Debug, Error)]
+pub enum RenderError {
+    #[error(
+        "Could not detect what kind of file {0} is. \
+        Expected file to end in .rbxlx, .rbxl, .rbxmx or .rbxm."
+    )]
+    UnknownFileKind(PathBuf),
+
+    #[error("Could not read {path}")]
+    FileRead {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("Could not decode binary file")]
+    BinaryDecode(#[from] rbx_binary::DecodeError),
+
+    #[error("Could not decode XML file")]
+    XmlDecode(#[from] rbx_xml::DecodeError),
+
+    #[error("Could not read config file {path}")]
+    ConfigRead {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("JSON formatting issue with config file")]
+    ConfigParse(#[from] serde_json::Error),
+
+    #[error("Invalid config: {0}")]
+    InvalidConfig(String),
+
+    #[error("Could not read texture {path}")]
+    TextureRead {
+        path: PathBuf,
+        #[source]
+        source: image::ImageError,
+    },
+
+    #[error("Could not find workspace from file.")]
+    WorkspaceNotFound,
+
+    #[error("Nothing was selected to render.")]
+    NothingToRender,
+
+    #[error("Cannot render a {width}x{height} image.")]
+    InvalidImageSize { width: u32, height: u32 },
+
+    #[error(
+        "Invalid zoom range {min_zoom} to {max_zoom}. \
+        The minimum can't be above the maximum, and zoom can't go past {}.",
+        super::tiles::MAX_ZOOM
+    )]
+    InvalidZoomRange { min_zoom: u8, max_zoom: u8 },
+
+    #[error("Could not decode terrain")]
+    TerrainDecode(#[from] SmoothGridError),
+
+    #[error("Unable to find instance {name} in {parent}")]
+    InstanceNotFound { name: String, parent: String },
+
+    #[error("{path} does not have a {property} property")]
+    MissingProperty {
+        path: String,
+        property: &'static str,
+    },
+}
+
+impl RenderError {
+    pub(crate) fn missing_property(
+        dom: &WeakDom,
+        instance: &Instance,
+        property: &'static str,
+    ) -> Self {
+        RenderError::MissingProperty {
+            path: get_full_name(dom, instance),
+            property,
+        }
+    }
+
+    /// A short, instance independent description used to group skipped
+    /// instances in the summary.
+    fn reason(&self) -> String {
+        match self {
+            RenderError::InstanceNotFound { .. } => "config path not found".to_owned(),
+            RenderError::MissingProperty { property, .. } => format!("missing {}", property),
+            other => other.to_string(),
+        }
+    }
+}
+
+/// What to do when an instance can't be drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorPolicy {
+    /// Leave the instance out, warn, and keep rendering.
+    #[default]
+    Skip,
+    /// Stop the render with an error.
+    Fail,
+}
+
+impl FromStr for ErrorPolicy {
+    type Err = ErrorPolicyParseError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        match source {
+            "skip" => Ok(ErrorPolicy::Skip),
+            "fail" => Ok(ErrorPolicy::Fail),
+            _ => Err(ErrorPolicyParseError {
+                attempted: source.to_owned(),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("Invalid error policy '{attempted}'. Valid values are: skip, fail")]
+pub struct ErrorPolicyParseError {
+    attempted: String,
+}
+
+/// Keeps count of the instances left out of a render, and why.
+#[derive(Debug, Default)]
+pub(crate) struct SkipReport {
+    reasons: BTreeMap<String, usize>,
+}
+
+impl SkipReport {
+    /// Records a failure, or hands it back if the policy says to stop.
+    pub(crate) fn handle(
+        &mut self,
+        policy: ErrorPolicy,
+        error: RenderError,
+    ) -> Result<(), RenderError> {
+        if policy == ErrorPolicy::Fail {
+            return Err(error);
+        }
+
+        log::debug!("Skipping: {}", error);
+        *self.reasons.entry(error.reason()).or_insert(0) += 1;
+        Ok(())
+    }
+
+    pub(crate) fn log_summary(&self) {
+        let total: usize = self.reasons.values().sum();
+        if total == 0 {
+            return;
+        }
+
+        log::warn!("Skipped {} instances that could not be drawn:", total);
+        for (reason, count) in self.reasons.iter() {
+            log::warn!("  {} x {}", count, reason);
+        }
+        log::warn!("Run with --verbose to see each one, or --on-error fail to stop at the first.");
+    }
+}
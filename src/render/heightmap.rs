@@ -0,0 +1,141 @@
+use image::{ImageBuffer, Luma};
+use rayon::prelude::*;
+
+use super::scene::{PartDrawData, TerrainLayer, BAND_HEIGHT};
+use super::view::View;
+
+/// The height of the highest surface under each pixel of an image.
+///
+/// Heights are worked out exactly at the center of every pixel, so a part
+/// only shows up where it is really the top-most thing, whatever order
+/// parts are drawn in.
+#[derive(Debug, Clone)]
+pub struct Heightmap {
+    width: u32,
+    height: u32,
+    /// Heights in studs, row by row, `None` where there is nothing
+    heights: Vec<Option<f32>>,
+}
+
+impl Heightmap {
+    /// Works out the map a band of rows at a time, in parallel.
+    pub(crate) fn from_layers(
+        width: u32,
+        height: u32,
+        terrain: Option<&TerrainLayer>,
+        parts: &[PartDrawData],
+        view: View,
+    ) -> Heightmap {
+        let mut heights = vec![None; (width * height) as usize];
+        let to_world = |px: u32, pz: u32| {
+            (
+                (px as f32 + 0.5f32 - view.offset_x) / view.scale,
+                (pz as f32 + 0.5f32 - view.offset_z) / view.scale,
+            )
+        };
+
+        heights
+            .par_chunks_mut((width * BAND_HEIGHT) as usize)
+            .enumerate()
+            .for_each(|(index, rows)| {
+                let top = index as u32 * BAND_HEIGHT;
+                let bottom = top + rows.len() as u32 / width;
+                let pixel = |px: u32, pz: u32| ((pz - top) * width + px) as usize;
+
+                if let Some(terrain) = terrain {
+                    for pz in top..bottom {
+                        for px in 0..width {
+                            let (x, z) = to_world(px, pz);
+                            rows[pixel(px, pz)] = terrain.top_at(x, z);
+                        }
+                    }
+                }
+
+                let band = View {
+                    offset_z: view.offset_z - top as f32,
+                    ..view
+                }
+                .to_world(width as f32, (bottom - top) as f32);
+                for part in parts.iter() {
+                    let bounds = part.bounds();
+                    if !bounds.intersects(&band) {
+                        continue;
+                    }
+
+                    // only the pixels under the part's box need testing.
+                    let (min_x, min_z) = view.project(bounds.min_x, bounds.min_z);
+                    let (max_x, max_z) = view.project(bounds.max_x, bounds.max_z);
+                    let clamp = |value: f32, low: u32, high: u32| {
+                        (value.max(low as f32) as u32).clamp(low, high)
+                    };
+
+                    for pz in clamp(min_z.floor(), top, bottom)..clamp(max_z.ceil(), top, bottom) {
+                        for px in clamp(min_x.floor(), 0, width)..clamp(max_x.ceil(), 0, width) {
+                            let (x, z) = to_world(px, pz);
+                            if let Some(height) = part.top_at(x, z) {
+                                let pixel = &mut rows[pixel(px, pz)];
+                                match pixel {
+                                    Some(current) if *current >= height => {}
+                                    _ => *pixel = Some(height),
+                                }
+                            }
+                        }
+                    }
+                }
+            });
+
+        Heightmap {
+            width,
+            height,
+            heights,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The height in studs of the surface under a pixel.
+    pub fn get(&self, x: u32, z: u32) -> Option<f32> {
+        if x >= self.width || z >= self.height {
+            return None;
+        }
+
+        self.heights[(z * self.width + x) as usize]
+    }
+
+    /// The lowest and highest surfaces in the map, in studs.
+    pub fn range(&self) -> Option<(f32, f32)> {
+        self.heights.iter().flatten().fold(None, |range, &height| {
+            Some(match range {
+                None => (height, height),
+                Some((low, high)) => (low.min(height), high.max(height)),
+            })
+        })
+    }
+
+    /// Converts the map into a 16 bit grayscale image, where `low` studs is
+    /// 1 and `high` studs is 65535. Pixels with nothing under them are 0, and
+    /// heights outside the range are clamped to it.
+    pub fn to_image(&self, low: f32, high: f32) -> ImageBuffer<Luma<u16>, Vec<u16>> {
+        let range = (high - low).max(f32::EPSILON);
+        let data = self
+            .heights
+            .iter()
+            .map(|height| match height {
+                Some(height) => {
+                    let t = ((height - low) / range).clamp(0f32, 1f32);
+                    1 + (t * (u16::MAX - 1) as f32).round() as u16
+                }
+                None => 0,
+            })
+            .collect();
+
+        // the data is built from the same width and height.
+        ImageBuffer::from_raw(self.width, self.height, data).unwrap()
+    }
+}
@@ -0,0 +1,497 @@
+//! Rendering places and models into images.
+//!
+//! This is what the `build-map` command runs on, and can be used directly
+//! by other tools:
+//!
+//! ```no_run
+//! use road_render::render::{Framing, MapRenderer, RenderConfig, ViewParams};
+//!
+//! let config = RenderConfig::from_path("config.json".as_ref())?;
+//! let params = ViewParams {
+//!     width: 1024,
+//!     height: 1024,
+//!     framing: Framing::Fit { margin: 16f32 },
+//! };
+//!
+//! let image = MapRenderer::new(config).render_file("place.rbxl".as_ref(), &params)?;
+//! image.save("map.png")?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+mod band;
+mod config;
+mod error;
+mod heightmap;
+mod palette;
+mod progress;
+mod scene;
+mod selector;
+mod shading;
+mod source;
+mod style;
+mod tiles;
+mod view;
+
+pub use band::HeightBand;
+pub use config::{RenderConfig, RenderRule, RenderView};
+pub use error::{ErrorPolicy, ErrorPolicyParseError, RenderError};
+pub use heightmap::Heightmap;
+pub use palette::{material_name, PaletteEntry};
+pub use progress::{NoProgress, Progress, Stage};
+pub use scene::Scene;
+pub use selector::{AttributeSelector, NamePattern, Selector};
+pub use shading::{GradientStop, HeightShading, Hillshade};
+pub use source::{load_dom, load_dom_with_progress, FileKind};
+pub use style::{ColorAdjustment, HatchStyle, PartStyle, StrokeStyle};
+pub use tiles::{TileId, TileParams, TilePyramid};
+pub use view::{Framing, View, ViewParams};
+
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+use image::RgbaImage;
+use rayon::prelude::*;
+use rbx_dom_weak::WeakDom;
+use rbx_types::{Ref, Variant};
+use tiny_skia::Pixmap;
+
+use crate::dom_lib::class;
+use crate::dom_lib::tree::{find_child, get_descendants, get_full_name};
+use crate::math_lib::bounds::Bounds;
+use crate::output::raster;
+use crate::terrain::material;
+use crate::terrain::smooth_grid::{self, TerrainMap, VOXEL_SIZE};
+use error::SkipReport;
+use palette::Palette;
+use scene::{PartDrawData, TerrainLayer};
+
+/// Turns a decoded place or model into an image, following a config.
+#[derive(Clone)]
+pub struct MapRenderer {
+    config: RenderConfig,
+    error_policy: ErrorPolicy,
+    progress: Arc<dyn Progress>,
+}
+
+impl MapRenderer {
+    pub fn new(config: RenderConfig) -> MapRenderer {
+        MapRenderer {
+            config,
+            error_policy: ErrorPolicy::default(),
+            progress: Arc::new(NoProgress),
+        }
+    }
+
+    /// Sets what to do with instances that can't be drawn.
+    pub fn with_error_policy(mut self, error_policy: ErrorPolicy) -> MapRenderer {
+        self.error_policy = error_policy;
+        self
+    }
+
+    /// Sets what to tell about how far along a render is. Scenes and tile
+    /// pyramids built by this renderer report to it while they draw.
+    pub fn with_progress(mut self, progress: Arc<dyn Progress>) -> MapRenderer {
+        self.progress = progress;
+        self
+    }
+
+    pub fn config(&self) -> &RenderConfig {
+        &self.config
+    }
+
+    /// A renderer for one of the config's views, with the same error policy
+    /// and progress reporting as this one.
+    pub fn for_view(&self, view: &RenderView) -> MapRenderer {
+        MapRenderer {
+            config: self.config.for_view(view),
+            ..self.clone()
+        }
+    }
+
+    /// Decodes a place or model file and renders it.
+    pub fn render_file(&self, path: &Path, params: &ViewParams) -> Result<RgbaImage, RenderError> {
+        let (dom, kind) = load_dom_with_progress(path, &*self.progress)?;
+        self.render_image(&dom, kind, params)
+    }
+
+    /// Renders a decoded dom into a pixmap.
+    pub fn render(
+        &self,
+        dom: &WeakDom,
+        kind: FileKind,
+        params: &ViewParams,
+    ) -> Result<Pixmap, RenderError> {
+        Ok(self.build_scene(dom, kind, params)?.draw())
+    }
+
+    /// Renders a decoded dom into an RGBA image buffer.
+    pub fn render_image(
+        &self,
+        dom: &WeakDom,
+        kind: FileKind,
+        params: &ViewParams,
+    ) -> Result<RgbaImage, RenderError> {
+        Ok(raster::pixmap_to_image(&self.render(dom, kind, params)?))
+    }
+
+    /// Renders a decoded dom into an SVG document.
+    pub fn render_svg(
+        &self,
+        dom: &WeakDom,
+        kind: FileKind,
+        params: &ViewParams,
+    ) -> Result<svg::Document, RenderError> {
+        Ok(self.build_scene(dom, kind, params)?.to_svg())
+    }
+
+    /// Picks out everything to draw from a decoded place or model and works
+    /// out where it goes in the image.
+    pub fn build_scene(
+        &self,
+        dom: &WeakDom,
+        kind: FileKind,
+        params: &ViewParams,
+    ) -> Result<Scene, RenderError> {
+        // configs built in code skip the checks in `RenderConfig::from_path`.
+        self.config.validate()?;
+        if params.width == 0 || params.height == 0 {
+            return Err(RenderError::InvalidImageSize {
+                width: params.width,
+                height: params.height,
+            });
+        }
+
+        let (parts, terrain) = self.collect(dom, kind)?;
+        let bounds = content_bounds(&parts, terrain.as_ref());
+
+        let width = params.width as f32;
+        let height = params.height as f32;
+
+        // a single model has no meaningful world position, so it is always
+        // framed to the requested image size.
+        let framing = match params.framing {
+            Framing::Manual { .. } if kind.is_model() => Framing::Fit { margin: 0f32 },
+            framing => framing,
+        };
+
+        let view = match framing {
+            Framing::Fit { margin } => {
+                let bounds = bounds.ok_or(RenderError::NothingToRender)?;
+                View::fit(&bounds, width, height, margin)
+            }
+            Framing::Manual {
+                offset_x,
+                offset_z,
+                scale,
+            } => {
+                let view = View {
+                    offset_x,
+                    offset_z,
+                    scale,
+                };
+
+                if let Some(bounds) = bounds {
+                    let image = view.to_world(width, height);
+                    if bounds.min_x < image.min_x
+                        || bounds.min_z < image.min_z
+                        || bounds.max_x > image.max_x
+                        || bounds.max_z > image.max_z
+                    {
+                        log::warn!(
+                            "Some parts fall outside of the image and will be clipped. \
+                            Pass --fit to frame everything automatically."
+                        );
+                    }
+                }
+
+                view
+            }
+        };
+
+        Ok(Scene {
+            width: params.width,
+            height: params.height,
+            parts,
+            terrain,
+            view,
+            progress: self.progress.clone(),
+        })
+    }
+
+    /// Picks out everything to draw and cuts it into a pyramid of map tiles
+    /// that covers all of it.
+    pub fn build_tiles(
+        &self,
+        dom: &WeakDom,
+        kind: FileKind,
+        params: &TileParams,
+    ) -> Result<TilePyramid, RenderError> {
+        self.config.validate()?;
+        params.validate()?;
+
+        let (parts, terrain) = self.collect(dom, kind)?;
+        let bounds =
+            content_bounds(&parts, terrain.as_ref()).ok_or(RenderError::NothingToRender)?;
+
+        Ok(TilePyramid::new(
+            parts,
+            terrain,
+            &bounds,
+            *params,
+            self.progress.clone(),
+        ))
+    }
+
+    /// Reads every part and the terrain selected by the config out of the
+    /// dom.
+    ///
+    /// Binary and XML files both end up here, so the same place saved
+    /// either way renders identically.
+    fn collect(
+        &self,
+        dom: &WeakDom,
+        kind: FileKind,
+    ) -> Result<(Vec<PartDrawData>, Option<TerrainLayer>), RenderError> {
+        // textures are only loaded if something is going to use them.
+        let uses_palette = if self.config.draw_everything {
+            self.config.use_palette
+        } else {
+            self.config.world_files.iter().any(|rule| rule.use_palette)
+        };
+        let palette = if uses_palette {
+            Palette::load(&self.config.palette)?
+        } else {
+            Palette::default()
+        };
+
+        let mut skipped = SkipReport::default();
+        let mut parts: Vec<PartDrawData> = Vec::new();
+        if self.config.draw_everything {
+            // draw everything :)
+            let descendants = search_root(dom, kind, &[])?;
+            self.progress
+                .start(Stage::Collecting, Some(descendants.len() as u64));
+
+            // parts are read in parallel, but kept in file order.
+            let found: Vec<Result<PartDrawData, RenderError>> = descendants
+                .par_iter()
+                .map(|iref| dom.get_by_ref(*iref).unwrap())
+                .inspect(|_| self.progress.advance(1))
+                // every BasePart is drawn from its Size, which for meshes
+                // and unions is the size of their bounding box.
+                .filter(|part| class::is_drawable_part(&part.class))
+                .map(|part| {
+                    let (color, texture) = if self.config.use_palette {
+                        palette.paint(part, || scene::part_color(dom, part))?
+                    } else {
+                        (scene::part_color(dom, part)?, None)
+                    };
+
+                    PartDrawData::from_instance(dom, part, color)
+                        .map(|data| PartDrawData { texture, ..data })
+                })
+                .collect();
+
+            for result in found {
+                match result {
+                    Ok(data) if in_band(&data, &self.config.band) => parts.push(data),
+                    Ok(_) => {}
+                    Err(error) => skipped.handle(self.error_policy, error)?,
+                }
+            }
+        } else {
+            // get world files and iterate through.
+            self.progress.start(Stage::Collecting, Some(0));
+            for (index, rule) in self.config.world_files.iter().enumerate() {
+                let descendants = match search_root(dom, kind, &rule.dir) {
+                    Ok(descendants) => descendants,
+                    Err(error @ RenderError::InstanceNotFound { .. }) => {
+                        skipped.handle(self.error_policy, error)?;
+                        continue;
+                    }
+                    Err(error) => return Err(error),
+                };
+
+                self.progress.grow(descendants.len() as u64);
+                let found: Vec<Result<PartDrawData, RenderError>> = descendants
+                    .par_iter()
+                    .map(|iref| dom.get_by_ref(*iref).unwrap())
+                    .inspect(|_| self.progress.advance(1))
+                    .filter(|part| rule.matches(dom, part))
+                    .map(|part| {
+                        let (color, texture) = rule.paint_for(dom, part, &palette)?;
+                        PartDrawData::from_instance(dom, part, color).map(|data| PartDrawData {
+                            texture,
+                            layer: rule.layer,
+                            style: rule.style.clone(),
+                            ..data
+                        })
+                    })
+                    .collect();
+
+                let band = rule.band.or(&self.config.band);
+                let before = parts.len();
+                for result in found {
+                    match result {
+                        Ok(data) if in_band(&data, &band) => parts.push(data),
+                        Ok(_) => {}
+                        Err(error) => skipped.handle(self.error_policy, error)?,
+                    }
+                }
+
+                log::info!(
+                    "world_files[{}] matched {} parts",
+                    index,
+                    parts.len() - before
+                );
+            }
+        }
+
+        self.progress.finish();
+        skipped.log_summary();
+
+        if let Some(shading) = &self.config.height_shading {
+            shading.apply(&mut parts);
+        }
+
+        // parts are drawn in order, so this decides what ends up on top.
+        // the sort is stable, leaving ties in the order they were found.
+        if self.config.sort_by_height {
+            parts.sort_by(|a, b| a.layer.cmp(&b.layer).then(a.top().total_cmp(&b.top())));
+        } else {
+            parts.sort_by_key(|part| part.layer);
+        }
+
+        let mut terrain = if self.config.draw_terrain {
+            read_terrain(dom)?
+        } else {
+            None
+        };
+
+        // only the surface of each column is known, so columns are kept or
+        // dropped by the height of their surface.
+        if let Some(terrain) = terrain.as_mut() {
+            let band = self.config.band;
+            if !band.is_open() {
+                terrain.retain(|column| band.contains(column.height));
+            }
+        }
+
+        // terrain is drawn ahead of time at one pixel per voxel column, and
+        // scaled into place when the scene is drawn.
+        let terrain = match terrain {
+            Some(terrain) => {
+                let terrain_colors = &self.config.terrain_colors;
+                let color_of = |id: u8| {
+                    material::material_name(id)
+                        .and_then(|name| terrain_colors.get(name))
+                        .filter(|color| color.len() == 4)
+                        .map(|color| [color[0], color[1], color[2], color[3]])
+                        .unwrap_or_else(|| material::default_color(id))
+                };
+
+                terrain.to_pixmap(color_of).map(|pixmap| TerrainLayer {
+                    pixmap,
+                    min_x: terrain.min_x as f32 * VOXEL_SIZE,
+                    min_z: terrain.min_z as f32 * VOXEL_SIZE,
+                    map: terrain,
+                })
+            }
+            None => None,
+        };
+
+        Ok((parts, terrain))
+    }
+}
+
+/// Whether any of a part's bounding box falls within a height band.
+fn in_band(part: &PartDrawData, band: &HeightBand) -> bool {
+    let (bottom, top) = part.height_range();
+    band.overlaps(bottom, top)
+}
+
+impl fmt::Debug for MapRenderer {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter
+            .debug_struct("MapRenderer")
+            .field("config", &self.config)
+            .field("error_policy", &self.error_policy)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The area of the world covered by everything that will be drawn.
+fn content_bounds(parts: &[PartDrawData], terrain: Option<&TerrainLayer>) -> Option<Bounds> {
+    parts
+        .iter()
+        .map(|part| part.bounds())
+        .chain(terrain.map(|terrain| terrain.bounds()))
+        .reduce(|a, b| a.union(&b))
+}
+
+/// Collects the instances a rule searches through: the descendants of
+/// `dir` if it is given, or of the Workspace otherwise.
+fn search_root(dom: &WeakDom, kind: FileKind, dir: &[String]) -> Result<Vec<Ref>, RenderError> {
+    if !dir.is_empty() {
+        return Ok(get_descendants(dom, &find_by_path(dom, dir)?));
+    }
+
+    let root = dom.root();
+    if kind.is_model() {
+        // models have no Workspace, their root instances are the things we
+        // want to see.
+        let mut descendants = Vec::new();
+        for child in root.children() {
+            descendants.push(*child);
+            descendants.extend(get_descendants(dom, child));
+        }
+        Ok(descendants)
+    } else {
+        let workspace = find_child(dom, root, "Workspace").ok_or(RenderError::WorkspaceNotFound)?;
+        Ok(get_descendants(dom, &workspace.referent()))
+    }
+}
+
+/// Follows a list of names down from the root of the dom.
+fn find_by_path(dom: &WeakDom, path: &[String]) -> Result<Ref, RenderError> {
+    let mut inst = dom.root();
+    for name in path.iter() {
+        inst = find_child(dom, inst, name).ok_or_else(|| RenderError::InstanceNotFound {
+            name: name.clone(),
+            parent: if inst.referent() == dom.root_ref() {
+                "the root of the file".to_owned()
+            } else {
+                get_full_name(dom, inst)
+            },
+        })?;
+    }
+
+    Ok(inst.referent())
+}
+
+/// Decodes Workspace.Terrain into a top down map, if the place has any.
+fn read_terrain(dom: &WeakDom) -> Result<Option<TerrainMap>, RenderError> {
+    let terrain = find_child(dom, dom.root(), "Workspace").and_then(|workspace| {
+        workspace
+            .children()
+            .iter()
+            .map(|&x| dom.get_by_ref(x).unwrap())
+            .find(|x| x.class == "Terrain")
+    });
+
+    let grid = match terrain.and_then(|terrain| terrain.properties.get("SmoothGrid")) {
+        Some(Variant::BinaryString(v)) => v,
+        _ => {
+            log::warn!("draw_terrain is set, but the file has no terrain to draw.");
+            return Ok(None);
+        }
+    };
+
+    let map = smooth_grid::decode_top_down(grid.as_ref())?;
+    if map.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(map))
+}
@@ -0,0 +1,272 @@
+//! Coloring parts by their Material.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use rbx_dom_weak::Instance;
+use rbx_types::Variant;
+use serde::Deserialize;
+use tiny_skia::{FilterQuality, Pattern, Pixmap, Shader, SpreadMode, Transform};
+
+use super::error::RenderError;
+use super::view::View;
+use crate::output::raster;
+
+/// The values of the Material enum, and their names.
+const MATERIALS: [(u32, &str); 37] = [
+    (256, "Plastic"),
+    (272, "SmoothPlastic"),
+    (288, "Neon"),
+    (512, "Wood"),
+    (528, "WoodPlanks"),
+    (784, "Marble"),
+    (788, "Basalt"),
+    (800, "Slate"),
+    (804, "CrackedLava"),
+    (816, "Concrete"),
+    (820, "Limestone"),
+    (832, "Granite"),
+    (836, "Pavement"),
+    (848, "Brick"),
+    (864, "Pebble"),
+    (880, "Cobblestone"),
+    (896, "Rock"),
+    (912, "Sandstone"),
+    (1040, "CorrodedMetal"),
+    (1056, "DiamondPlate"),
+    (1072, "Foil"),
+    (1088, "Metal"),
+    (1280, "Grass"),
+    (1284, "LeafyGrass"),
+    (1296, "Sand"),
+    (1312, "Fabric"),
+    (1328, "Snow"),
+    (1344, "Mud"),
+    (1360, "Ground"),
+    (1376, "Asphalt"),
+    (1392, "Salt"),
+    (1536, "Ice"),
+    (1552, "Glacier"),
+    (1568, "Glass"),
+    (1584, "ForceField"),
+    (1792, "Air"),
+    (2048, "Water"),
+];
+
+/// Map colors for materials that say what the ground is. Plastic, Neon,
+/// Glass and the like are left out, so those parts keep their own color.
+const DEFAULT_PALETTE: [(&str, [u8; 4]); 29] = [
+    ("Wood", [150, 110, 70, 255]),
+    ("WoodPlanks", [160, 120, 80, 255]),
+    ("Marble", [210, 205, 200, 255]),
+    ("Basalt", [70, 65, 65, 255]),
+    ("Slate", [90, 95, 100, 255]),
+    ("CrackedLava", [140, 60, 40, 255]),
+    ("Concrete", [165, 165, 160, 255]),
+    ("Limestone", [205, 200, 180, 255]),
+    ("Granite", [140, 130, 125, 255]),
+    ("Pavement", [150, 150, 145, 255]),
+    ("Brick", [160, 85, 65, 255]),
+    ("Pebble", [155, 150, 140, 255]),
+    ("Cobblestone", [125, 120, 115, 255]),
+    ("Rock", [110, 105, 100, 255]),
+    ("Sandstone", [200, 170, 120, 255]),
+    ("CorrodedMetal", [110, 90, 75, 255]),
+    ("DiamondPlate", [150, 155, 160, 255]),
+    ("Metal", [130, 135, 140, 255]),
+    ("Grass", [100, 150, 70, 255]),
+    ("LeafyGrass", [90, 140, 60, 255]),
+    ("Sand", [220, 200, 150, 255]),
+    ("Snow", [240, 245, 250, 255]),
+    ("Mud", [110, 85, 60, 255]),
+    ("Ground", [120, 100, 70, 255]),
+    ("Asphalt", [60, 60, 65, 255]),
+    ("Salt", [225, 225, 220, 255]),
+    ("Ice", [180, 215, 235, 255]),
+    ("Glacier", [170, 210, 230, 255]),
+    ("Water", [60, 110, 170, 255]),
+];
+
+/// The value of Enum.Material.Plastic, which parts without a Material have.
+const PLASTIC: u32 = 256;
+
+pub fn material_name(value: u32) -> Option<&'static str> {
+    MATERIALS
+        .iter()
+        .find(|(x, _)| *x == value)
+        .map(|(_, name)| *name)
+}
+
+fn material_value(name: &str) -> Option<u32> {
+    MATERIALS
+        .iter()
+        .find(|(_, x)| *x == name)
+        .map(|(value, _)| *value)
+}
+
+/// What a material is drawn with, as written in a config.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum PaletteEntry {
+    /// An RGBA color
+    Color(Vec<u8>),
+    /// An image repeated across the part
+    Texture {
+        /// Path to the image, relative to the config file
+        texture: PathBuf,
+        /// Width of one repeat of the image in studs
+        #[serde(default = "default_texture_size")]
+        size: f32,
+    },
+}
+
+fn default_texture_size() -> f32 {
+    16f32
+}
+
+/// An image repeated across parts, anchored to the world so it lines up
+/// between neighbouring parts.
+#[derive(Debug)]
+pub(crate) struct Texture {
+    pixmap: Pixmap,
+    size: f32,
+}
+
+impl Texture {
+    /// A shader that paints the texture at its world size under `view`.
+    pub(crate) fn shader(&self, view: View, opacity: f32) -> Shader<'_> {
+        let scale = view.scale * self.size / self.pixmap.width() as f32;
+        Pattern::new(
+            self.pixmap.as_ref(),
+            SpreadMode::Repeat,
+            FilterQuality::Bilinear,
+            opacity,
+            Transform::from_row(scale, 0f32, 0f32, scale, view.offset_x, view.offset_z),
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+enum PaletteFill {
+    Color([u8; 4]),
+    Texture(Arc<Texture>),
+}
+
+/// The built in palette with a config's entries on top, textures loaded.
+#[derive(Debug, Default)]
+pub(crate) struct Palette {
+    fills: HashMap<u32, PaletteFill>,
+}
+
+impl Palette {
+    pub(crate) fn load(entries: &HashMap<String, PaletteEntry>) -> Result<Palette, RenderError> {
+        let mut fills = HashMap::new();
+        for (name, color) in DEFAULT_PALETTE.iter() {
+            // every name in the default palette is a material.
+            fills.insert(material_value(name).unwrap(), PaletteFill::Color(*color));
+        }
+
+        for (name, entry) in entries.iter() {
+            let material = material_value(name).ok_or_else(|| {
+                RenderError::InvalidConfig(format!("unknown material {} in palette", name))
+            })?;
+
+            let fill = match entry {
+                PaletteEntry::Color(color) => {
+                    PaletteFill::Color([color[0], color[1], color[2], color[3]])
+                }
+                PaletteEntry::Texture { texture, size } => {
+                    let image = image::open(texture)
+                        .map_err(|source| RenderError::TextureRead {
+                            path: texture.clone(),
+                            source,
+                        })?
+                        .into_rgba8();
+
+                    PaletteFill::Texture(Arc::new(Texture {
+                        pixmap: raster::image_to_pixmap(&image).ok_or_else(|| {
+                            RenderError::InvalidConfig(format!("texture for {} is empty", name))
+                        })?,
+                        size: *size,
+                    }))
+                }
+            };
+
+            fills.insert(material, fill);
+        }
+
+        Ok(Palette { fills })
+    }
+
+    /// Picks the color and texture to draw a part with: its material's
+    /// entry if the palette has one, otherwise whatever `fallback` gives.
+    ///
+    /// Palette entries keep the part's Transparency.
+    pub(crate) fn paint<F>(
+        &self,
+        part: &Instance,
+        fallback: F,
+    ) -> Result<(Vec<u8>, Option<Arc<Texture>>), RenderError>
+    where
+        F: FnOnce() -> Result<Vec<u8>, RenderError>,
+    {
+        let material = match part.properties.get("Material") {
+            Some(Variant::Enum(v)) => v.to_u32(),
+            _ => PLASTIC,
+        };
+
+        let opacity = match part.properties.get("Transparency") {
+            Some(Variant::Float32(v)) => 1f32 - v,
+            _ => 1f32,
+        };
+
+        match self.fills.get(&material) {
+            Some(PaletteFill::Color(color)) => Ok((
+                vec![
+                    color[0],
+                    color[1],
+                    color[2],
+                    (color[3] as f32 * opacity).round() as u8,
+                ],
+                None,
+            )),
+            Some(PaletteFill::Texture(texture)) => Ok((
+                vec![255, 255, 255, (255f32 * opacity).round() as u8],
+                Some(texture.clone()),
+            )),
+            None => Ok((fallback()?, None)),
+        }
+    }
+}
+
+/// Checks a palette from a config without loading its textures.
+pub(crate) fn validate(entries: &HashMap<String, PaletteEntry>) -> Result<(), RenderError> {
+    for (name, entry) in entries.iter() {
+        if material_value(name).is_none() {
+            return Err(RenderError::InvalidConfig(format!(
+                "unknown material {} in palette",
+                name
+            )));
+        }
+
+        match entry {
+            PaletteEntry::Color(color) if color.len() != 4 => {
+                return Err(RenderError::InvalidConfig(format!(
+                    "palette color for {} must have 4 values (RGBA), found {}",
+                    name,
+                    color.len()
+                )));
+            }
+            PaletteEntry::Texture { size, .. } if *size <= 0f32 => {
+                return Err(RenderError::InvalidConfig(format!(
+                    "texture size for {} must be above 0",
+                    name
+                )));
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,60 @@
+use std::io::{self, Read};
+
+/// The steps of a render that report progress.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// Reading and decoding the place file, counted in bytes
+    Decoding,
+    /// Looking through the instances selected by the config, counted in
+    /// instances
+    Collecting,
+    /// Drawing parts into the image, counted in parts
+    Drawing,
+    /// Drawing map tiles, counted in tiles
+    DrawingTiles,
+}
+
+/// Told how far along a render is, eg. to show a progress bar.
+///
+/// Work is spread across threads, so the methods can be called from several
+/// threads at once.
+pub trait Progress: Send + Sync {
+    /// A stage has started. `total` is the number of steps in it, if it is
+    /// known ahead of time.
+    fn start(&self, stage: Stage, total: Option<u64>);
+
+    /// More steps were found for the current stage.
+    fn grow(&self, _steps: u64) {}
+
+    /// Steps of the current stage are done.
+    fn advance(&self, steps: u64);
+
+    /// The current stage is done.
+    fn finish(&self);
+}
+
+/// Ignores progress, for when nobody is watching.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoProgress;
+
+impl Progress for NoProgress {
+    fn start(&self, _stage: Stage, _total: Option<u64>) {}
+
+    fn advance(&self, _steps: u64) {}
+
+    fn finish(&self) {}
+}
+
+/// Reports the bytes read through it as progress.
+pub(crate) struct ProgressReader<'a, R> {
+    pub(crate) inner: R,
+    pub(crate) progress: &'a dyn Progress,
+}
+
+impl<R: Read> Read for ProgressReader<'_, R> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buffer)?;
+        self.progress.advance(read as u64);
+        Ok(read)
+    }
+}
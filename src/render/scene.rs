@@ -0,0 +1,668 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use rayon::prelude::*;
+use rbx_dom_weak::{Instance, WeakDom};
+use rbx_types::{CFrame, Variant, Vector3};
+use svg::node::element::{Definitions, Pattern, Polygon, Rectangle, Title};
+use svg::node::Text as TextNode;
+use svg::Document;
+use tiny_skia::{Paint, PathBuilder, Pixmap, PixmapMut, PixmapPaint, Transform};
+
+use super::error::RenderError;
+use super::heightmap::Heightmap;
+use super::palette::Texture;
+use super::progress::{Progress, Stage};
+use super::style::PartStyle;
+use super::view::View;
+use crate::dom_lib::tree::get_full_name;
+use crate::math_lib::bounds::Bounds;
+use crate::math_lib::footprint::{footprint, top_normal, PartShape};
+use crate::math_lib::surface::top_at;
+use crate::terrain::smooth_grid::{TerrainMap, VOXEL_SIZE};
+
+/// Images are drawn in bands of this many rows, each of which can go to a
+/// different thread.
+///
+/// Bands are the same size however many threads there are, one included, so
+/// an image comes out byte for byte the same on every machine. It is not
+/// quite the same as drawing the whole image in one go though, as tiny-skia
+/// cuts paths at the edges of each band and the cut edges anti-alias a
+/// little differently.
+pub(crate) const BAND_HEIGHT: u32 = 64;
+
+/// The properties of a part needed to draw it, read out of the dom.
+pub(crate) struct PartDrawData {
+    /// Full name of the instance, eg. `Workspace.Map.Roads.Base`
+    pub(crate) path: String,
+    pub(crate) cframe: CFrame,
+    pub(crate) size: Vector3,
+    pub(crate) shape: PartShape,
+    pub(crate) color: Vec<u8>,
+    /// Parts on higher layers are drawn over parts on lower ones
+    pub(crate) layer: i32,
+    pub(crate) style: PartStyle,
+    /// Painted instead of the color, which then only sets the opacity
+    pub(crate) texture: Option<Arc<Texture>>,
+}
+
+impl PartDrawData {
+    pub(crate) fn from_instance(
+        dom: &WeakDom,
+        part: &Instance,
+        color: Vec<u8>,
+    ) -> Result<PartDrawData, RenderError> {
+        let cframe = match part.properties.get("CFrame") {
+            Some(Variant::CFrame(v)) => *v,
+            _ => return Err(RenderError::missing_property(dom, part, "CFrame")),
+        };
+
+        let size = match part.properties.get("Size") {
+            Some(Variant::Vector3(v)) => *v,
+            _ => return Err(RenderError::missing_property(dom, part, "Size")),
+        };
+
+        // wedges are their own classes, everything else that isn't a box
+        // says so through Part.Shape.
+        let shape = match part.class.as_str() {
+            "WedgePart" => PartShape::Wedge,
+            "CornerWedgePart" => PartShape::CornerWedge,
+            _ => match part.properties.get("Shape") {
+                Some(Variant::Enum(v)) => PartShape::from_part_type(v.to_u32()),
+                _ => PartShape::Block,
+            },
+        };
+
+        Ok(PartDrawData {
+            path: get_full_name(dom, part),
+            cframe,
+            size,
+            shape,
+            color,
+            layer: 0,
+            style: PartStyle::default(),
+            texture: None,
+        })
+    }
+
+    /// The height of the highest point of the part.
+    pub(crate) fn top(&self) -> f32 {
+        self.height_range().1
+    }
+
+    /// The heights of the lowest and highest points of the part's bounding
+    /// box.
+    pub(crate) fn height_range(&self) -> (f32, f32) {
+        let rot = self.cframe.orientation;
+        let half_y = (rot.y.x.abs() * self.size.x
+            + rot.y.y.abs() * self.size.y
+            + rot.y.z.abs() * self.size.z)
+            * 0.5f32;
+        (
+            self.cframe.position.y - half_y,
+            self.cframe.position.y + half_y,
+        )
+    }
+
+    /// The direction the upper-most face of the part looks in.
+    pub(crate) fn top_normal(&self) -> Vector3 {
+        top_normal(self.shape, self.size, self.cframe.orientation)
+    }
+
+    /// The height of the top of the part at a world position, if the part
+    /// covers it.
+    pub(crate) fn top_at(&self, x: f32, z: f32) -> Option<f32> {
+        top_at(
+            self.shape,
+            self.cframe.position,
+            self.size,
+            self.cframe.orientation,
+            x,
+            z,
+        )
+    }
+
+    /// Whether anything drawn for the part can land inside `area`, allowing
+    /// for anti-aliasing and outlines that reach past its edges.
+    pub(crate) fn touches(&self, area: &Bounds, scale: f32) -> bool {
+        // miter joins reach out up to twice the stroke width at corners.
+        let stroke = self
+            .style
+            .stroke
+            .as_ref()
+            .map_or(0f32, |stroke| stroke.width * 2f32);
+        self.bounds()
+            .expand((stroke + 1f32) / scale)
+            .intersects(area)
+    }
+
+    /// The top down footprint of the part in world space.
+    pub(crate) fn bounds(&self) -> Bounds {
+        Bounds::from_part(self.cframe.position, self.size, self.cframe.orientation)
+    }
+
+    /// The outline of the part in pixel coordinates.
+    fn outline(&self, view: View) -> Vec<(f32, f32)> {
+        // every corner of the part goes through the full rotation, so
+        // pitched and rolled parts keep their true outline.
+        footprint(
+            self.shape,
+            self.cframe.position,
+            self.size,
+            self.cframe.orientation,
+        )
+        .into_iter()
+        .map(|(x, z)| view.project(x, z))
+        .collect()
+    }
+}
+
+/// Terrain drawn at one pixel per voxel column, and where its top left
+/// corner sits in the world.
+pub(crate) struct TerrainLayer {
+    pub(crate) pixmap: Pixmap,
+    pub(crate) min_x: f32,
+    pub(crate) min_z: f32,
+    /// The columns the pixmap was drawn from, kept for their heights
+    pub(crate) map: TerrainMap,
+}
+
+impl TerrainLayer {
+    pub(crate) fn bounds(&self) -> Bounds {
+        Bounds::new(
+            self.min_x,
+            self.min_z,
+            self.min_x + self.pixmap.width() as f32 * VOXEL_SIZE,
+            self.min_z + self.pixmap.height() as f32 * VOXEL_SIZE,
+        )
+    }
+
+    /// The height of the terrain surface at a world position.
+    pub(crate) fn top_at(&self, x: f32, z: f32) -> Option<f32> {
+        let column_x = ((x - self.min_x) / VOXEL_SIZE).floor();
+        let column_z = ((z - self.min_z) / VOXEL_SIZE).floor();
+        if column_x < 0f32 || column_z < 0f32 {
+            return None;
+        }
+
+        self.map
+            .column(column_x as u32, column_z as u32)
+            .map(|column| column.height)
+    }
+
+    /// Scales the layer into place under `view`.
+    fn draw(&self, pixmap: &mut PixmapMut, view: View, shift: Transform) {
+        let (x, z) = view.project(self.min_x, self.min_z);
+        let voxel_scale = VOXEL_SIZE * view.scale;
+        pixmap.draw_pixmap(
+            0,
+            0,
+            self.pixmap.as_ref(),
+            &PixmapPaint::default(),
+            Transform::from_row(voxel_scale, 0f32, 0f32, voxel_scale, x, z).post_concat(shift),
+            None,
+        );
+    }
+}
+
+/// Everything that will be drawn, already placed in the image.
+pub struct Scene {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) parts: Vec<PartDrawData>,
+    pub(crate) terrain: Option<TerrainLayer>,
+    pub(crate) view: View,
+    pub(crate) progress: Arc<dyn Progress>,
+}
+
+impl Scene {
+    /// How world coordinates map onto the image.
+    pub fn view(&self) -> View {
+        self.view
+    }
+
+    /// Rasterizes the scene into a new pixmap.
+    ///
+    /// The image is split into bands that are drawn in parallel, straight
+    /// into the rows of the pixmap they cover.
+    pub fn draw(&self) -> Pixmap {
+        // the size is checked when the scene is built.
+        let mut pixmap = Pixmap::new(self.width, self.height).unwrap();
+        let row_bytes = self.width as usize * 4;
+
+        // parts are sorted into bands first, so the total is known up front.
+        let bands: Vec<Vec<&PartDrawData>> = (0..self.height)
+            .into_par_iter()
+            .step_by(BAND_HEIGHT as usize)
+            .map(|top| {
+                let view = self.view;
+                let area = Bounds::new(
+                    -view.offset_x / view.scale,
+                    (top as f32 - view.offset_z) / view.scale,
+                    (self.width as f32 - view.offset_x) / view.scale,
+                    ((top + BAND_HEIGHT) as f32 - view.offset_z) / view.scale,
+                );
+                self.parts
+                    .iter()
+                    .filter(|part| part.touches(&area, view.scale))
+                    .collect()
+            })
+            .collect();
+
+        let total = bands.iter().map(|parts| parts.len() as u64).sum();
+        self.progress.start(Stage::Drawing, Some(total));
+
+        pixmap
+            .data_mut()
+            .par_chunks_mut(row_bytes * BAND_HEIGHT as usize)
+            .zip(bands.par_iter())
+            .enumerate()
+            .for_each(|(index, (rows, parts))| {
+                // each band is drawn as an image of its own. Parts are placed
+                // with the full view and only shifted up at the end, so every
+                // band sees the same pixel coordinates a single draw would.
+                let top = index as u32 * BAND_HEIGHT;
+                let height = (rows.len() / row_bytes) as u32;
+                let parts = parts.iter().copied().inspect(|_| self.progress.advance(1));
+                let shift = Transform::from_translate(0f32, -(top as f32));
+
+                // the chunk holds exactly `height` whole rows.
+                let mut band = PixmapMut::from_bytes(rows, self.width, height).unwrap();
+                draw_layers(&mut band, self.terrain.as_ref(), parts, self.view, shift);
+            });
+
+        self.progress.finish();
+        pixmap
+    }
+
+    /// Finds the highest surface under every pixel of the image.
+    pub fn heightmap(&self) -> Heightmap {
+        Heightmap::from_layers(
+            self.width,
+            self.height,
+            self.terrain.as_ref(),
+            &self.parts,
+            self.view,
+        )
+    }
+
+    /// Builds an SVG of the scene, with one polygon per part.
+    ///
+    /// Each polygon is titled with the full path of the instance it came
+    /// from, so parts can be picked out in vector editors.
+    pub fn to_svg(&self) -> Document {
+        if self.terrain.is_some() {
+            log::warn!("Terrain is not included in SVG output.");
+        }
+        if self.parts.iter().any(|part| part.texture.is_some()) {
+            log::warn!("Palette textures are not included in SVG output.");
+        }
+
+        let mut document = Document::new()
+            .set("viewBox", (0, 0, self.width, self.height))
+            .set("width", self.width)
+            .set("height", self.height);
+
+        let mut used_ids: HashSet<String> = HashSet::new();
+        let mut hatch_ids: HashMap<String, String> = HashMap::new();
+        let mut patterns = Vec::new();
+        let mut polygons = Vec::new();
+        for part in self.parts.iter() {
+            let points: Vec<String> = part
+                .outline(self.view)
+                .iter()
+                .map(|(x, z)| format!("{},{}", x, z))
+                .collect();
+
+            // ids have to be unique, but sibling parts often share a name.
+            let base_id = svg_id(&part.path);
+            let mut id = base_id.clone();
+            let mut uses = 1;
+            while !used_ids.insert(id.clone()) {
+                uses += 1;
+                id = format!("{}-{}", base_id, uses);
+            }
+
+            let points = points.join(" ");
+            let style = &part.style;
+
+            let mut polygon = Polygon::new()
+                .set("id", id)
+                .set("points", points.clone())
+                .set("fill", svg_color(&part.color))
+                .set(
+                    "fill-opacity",
+                    style.fill_alpha(part.color[3]) as f32 / 255f32,
+                )
+                .add(Title::new().add(TextNode::new(escape_xml(&part.path))));
+
+            if let Some(stroke) = &style.stroke {
+                polygon = polygon
+                    .set("stroke", svg_color(&stroke.color))
+                    .set("stroke-opacity", stroke.color[3] as f32 / 255f32)
+                    .set("stroke-width", stroke.width);
+
+                if !stroke.dash.is_empty() {
+                    let dash: Vec<String> = stroke.dash.iter().map(|x| x.to_string()).collect();
+                    polygon = polygon.set("stroke-dasharray", dash.join(" "));
+                }
+            }
+
+            polygons.push(polygon);
+
+            // hatching is a second polygon over the first, filled with a
+            // pattern shared by every part hatched the same way.
+            if let Some(hatch) = &style.hatch {
+                let color = hatch.color.as_deref().unwrap_or(&part.color);
+                let key = format!(
+                    "{:?} {} {} {}",
+                    color, hatch.spacing, hatch.width, hatch.angle
+                );
+                let next_id = format!("hatch-{}", hatch_ids.len() + 1);
+                let pattern_id = hatch_ids.entry(key).or_insert_with(|| {
+                    patterns.push(
+                        Pattern::new()
+                            .set("id", next_id.clone())
+                            .set("patternUnits", "userSpaceOnUse")
+                            .set("width", hatch.spacing)
+                            .set("height", hatch.spacing)
+                            .set("patternTransform", format!("rotate({})", hatch.angle))
+                            .add(
+                                Rectangle::new()
+                                    .set("width", hatch.spacing)
+                                    .set("height", hatch.width)
+                                    .set("fill", svg_color(color))
+                                    .set("fill-opacity", color[3] as f32 / 255f32),
+                            ),
+                    );
+                    next_id
+                });
+
+                polygons.push(
+                    Polygon::new()
+                        .set("points", points)
+                        .set("fill", format!("url(#{})", pattern_id)),
+                );
+            }
+        }
+
+        if !patterns.is_empty() {
+            let definitions = patterns
+                .into_iter()
+                .fold(Definitions::new(), |definitions, pattern| {
+                    definitions.add(pattern)
+                });
+            document = document.add(definitions);
+        }
+        for polygon in polygons {
+            document = document.add(polygon);
+        }
+
+        document
+    }
+}
+
+/// Turns an instance path into a valid XML id, replacing anything that
+/// can't appear in one with `_`.
+fn svg_id(path: &str) -> String {
+    let mut id: String = path
+        .chars()
+        .map(|x| match x {
+            x if x.is_alphanumeric() || matches!(x, '.' | '-' | '_') => x,
+            _ => '_',
+        })
+        .collect();
+
+    // ids can't start with a digit, `.` or `-`.
+    if !id.starts_with(|x: char| x.is_alphabetic() || x == '_') {
+        id.insert(0, '_');
+    }
+    id
+}
+
+/// Escapes text for use in SVG, which the svg crate leaves to its callers.
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for x in text.chars() {
+        match x {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(x),
+        }
+    }
+    escaped
+}
+
+/// Formats the RGB part of a color for SVG attributes.
+fn svg_color(color: &[u8]) -> String {
+    format!("rgb({},{},{})", color[0], color[1], color[2])
+}
+
+/// Draws terrain and parts into `pixmap`, placed by `view` and then moved by
+/// `shift`.
+pub(crate) fn draw_layers<'a, I>(
+    pixmap: &mut PixmapMut,
+    terrain: Option<&TerrainLayer>,
+    parts: I,
+    view: View,
+    shift: Transform,
+) where
+    I: Iterator<Item = &'a PartDrawData>,
+{
+    // terrain goes down first so that parts are composited over it.
+    if let Some(terrain) = terrain {
+        terrain.draw(pixmap, view, shift);
+    }
+
+    for part in parts {
+        draw_part_on_pixmap(pixmap, part, view, shift);
+    }
+}
+
+/// Reads a part's own color, with its transparency as the alpha.
+pub(crate) fn part_color(dom: &WeakDom, part: &Instance) -> Result<Vec<u8>, RenderError> {
+    let object_color = match part.properties.get("Color") {
+        Some(Variant::Color3uint8(v)) => v,
+        _ => return Err(RenderError::missing_property(dom, part, "Color")),
+    };
+
+    let object_transparency = match part.properties.get("Transparency") {
+        Some(Variant::Float32(v)) => v,
+        _ => return Err(RenderError::missing_property(dom, part, "Transparency")),
+    };
+
+    Ok(vec![
+        object_color.r,
+        object_color.g,
+        object_color.b,
+        ((1f32 - object_transparency) * 255f32).round() as u8,
+    ])
+}
+
+/// Fills the outline of a part, then hatches and outlines it if its style
+/// asks for it.
+fn draw_part_on_pixmap(map: &mut PixmapMut, part: &PartDrawData, view: View, shift: Transform) {
+    let color = &part.color;
+    let style = &part.style;
+
+    let mut pb = PathBuilder::new();
+    for (i, &(x, z)) in part.outline(view).iter().enumerate() {
+        if i == 0 {
+            pb.move_to(x, z);
+        } else {
+            pb.line_to(x, z);
+        }
+    }
+    pb.close();
+
+    let path = match pb.finish() {
+        Some(path) => path,
+        None => return,
+    };
+
+    let alpha = style.fill_alpha(color[3]);
+    if alpha > 0 {
+        let mut paint = Paint::default();
+        match &part.texture {
+            Some(texture) => paint.shader = texture.shader(view, alpha as f32 / 255f32),
+            None => paint.set_color_rgba8(color[0], color[1], color[2], alpha),
+        }
+        paint.anti_alias = true;
+
+        map.fill_path(&path, &paint, tiny_skia::FillRule::Winding, shift, None);
+    }
+
+    if let Some(hatch) = &style.hatch {
+        let hatch_color = hatch.color.as_deref().unwrap_or(color);
+        if let Some(tile) = hatch.tile(hatch_color) {
+            let paint = Paint {
+                shader: hatch.shader(&tile, view),
+                anti_alias: true,
+                ..Paint::default()
+            };
+
+            map.fill_path(&path, &paint, tiny_skia::FillRule::Winding, shift, None);
+        }
+    }
+
+    if let Some(stroke) = &style.stroke {
+        let mut paint = Paint::default();
+        let stroke_color = &stroke.color;
+        paint.set_color_rgba8(
+            stroke_color[0],
+            stroke_color[1],
+            stroke_color[2],
+            stroke_color[3],
+        );
+        paint.anti_alias = true;
+
+        map.stroke_path(&path, &paint, &stroke.to_stroke(), shift, None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rbx_types::Matrix3;
+
+    use super::*;
+    use crate::render::progress::NoProgress;
+    use crate::render::style::{HatchStyle, StrokeStyle};
+
+    /// Parts spun about every axis, far from the origin so positions lose
+    /// precision, and overlapping so blending order matters.
+    fn scene(style: PartStyle) -> Scene {
+        let parts = (0..300)
+            .map(|i| {
+                let i = i as f32;
+                let (yaw, pitch) = (i * 0.37f32, i * 0.11f32);
+                let (sin_y, cos_y) = yaw.sin_cos();
+                let (sin_p, cos_p) = pitch.sin_cos();
+                let orientation = Matrix3::new(
+                    Vector3::new(cos_y, sin_y * sin_p, sin_y * cos_p),
+                    Vector3::new(0f32, cos_p, -sin_p),
+                    Vector3::new(-sin_y, cos_y * sin_p, cos_y * cos_p),
+                );
+                let position = Vector3::new(
+                    10000f32 + (i * 7.3f32) % 200f32,
+                    i,
+                    -3000f32 + (i * 13.1f32) % 200f32,
+                );
+
+                PartDrawData {
+                    path: format!("Workspace.Part{}", i),
+                    cframe: CFrame::new(position, orientation),
+                    size: Vector3::new(4f32 + i % 9f32, 1f32, 30f32 - i % 20f32),
+                    shape: PartShape::Block,
+                    color: vec![(i as u32 * 40 % 256) as u8, 120, 200, 180],
+                    layer: 0,
+                    style: style.clone(),
+                    texture: None,
+                }
+            })
+            .collect();
+
+        let (width, height) = (700, 1110);
+        Scene {
+            width,
+            height,
+            parts,
+            terrain: None,
+            view: View::fit(
+                &Bounds::new(9990f32, -3010f32, 10210f32, -2790f32),
+                width as f32,
+                height as f32,
+                0f32,
+            ),
+            progress: Arc::new(NoProgress),
+        }
+    }
+
+    fn assert_same_on_any_thread_count(scene: Scene) {
+        let draw_on = |threads| {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap()
+                .install(|| scene.draw())
+        };
+
+        let one = draw_on(1);
+        for threads in [2, 3, 8] {
+            assert!(
+                one.data() == draw_on(threads).data(),
+                "drawing on {} threads differs from drawing on one",
+                threads
+            );
+        }
+    }
+
+    #[test]
+    fn same_on_any_thread_count() {
+        assert_same_on_any_thread_count(scene(PartStyle::default()));
+    }
+
+    #[test]
+    fn same_on_any_thread_count_with_strokes() {
+        assert_same_on_any_thread_count(scene(PartStyle {
+            stroke: Some(StrokeStyle {
+                color: vec![0, 0, 0, 255],
+                width: 1.5f32,
+                dash: vec![],
+            }),
+            ..PartStyle::default()
+        }));
+    }
+
+    #[test]
+    fn same_on_any_thread_count_with_hatching() {
+        assert_same_on_any_thread_count(scene(PartStyle {
+            hatch: Some(HatchStyle {
+                color: None,
+                spacing: 6f32,
+                width: 1f32,
+                angle: 30f32,
+            }),
+            ..PartStyle::default()
+        }));
+    }
+
+    #[test]
+    fn svg_escapes_names() {
+        let mut scene = scene(PartStyle::default());
+        scene.parts.truncate(3);
+        scene.parts[0].path = "Workspace.Road & <Sidewalk>".to_owned();
+        scene.parts[1].path = "Workspace.Road & <Sidewalk>".to_owned();
+        scene.parts[2].path = "1st \"Street\"".to_owned();
+
+        let svg = scene.to_svg().to_string();
+        assert!(svg.contains(r#"id="Workspace.Road____Sidewalk_""#));
+        assert!(svg.contains(r#"id="Workspace.Road____Sidewalk_-2""#));
+        assert!(svg.contains(r#"id="_1st__Street_""#));
+        assert!(svg.contains("Workspace.Road &amp; &lt;Sidewalk&gt;"));
+        assert!(svg.contains("1st &quot;Street&quot;"));
+        assert!(!svg.contains("<Sidewalk>"));
+    }
+}
@@ -0,0 +1,314 @@
+//! Matching instances against the `select` conditions of a config rule.
+
+use rbx_dom_weak::{Instance, WeakDom};
+use rbx_types::Variant;
+use regex::Regex;
+use serde::Deserialize;
+
+use super::error::RenderError;
+use crate::dom_lib::class;
+
+/// A condition an instance has to meet to be drawn by a rule.
+///
+/// In a config every selector is an object with a single key, eg.
+/// `{"class": "BasePart"}` or `{"not": {"tag": "Hidden"}}`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Selector {
+    /// The class of the instance is, or inherits from, this class
+    Class(String),
+    /// The instance has this CollectionService tag
+    Tag(String),
+    /// The instance has an attribute, optionally with a given value
+    Attribute(AttributeSelector),
+    /// The name of the instance matches a glob pattern, where `*` matches
+    /// any run of characters and `?` matches any single one
+    Name(String),
+    /// The name of the instance matches a regular expression
+    NameRegex(NamePattern),
+    /// Some ancestor of the instance has a name matching a glob pattern
+    Ancestor(String),
+    /// Every selector in the list matches
+    All(Vec<Selector>),
+    /// At least one selector in the list matches
+    Any(Vec<Selector>),
+    /// The selector does not match
+    Not(Box<Selector>),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AttributeSelector {
+    pub name: String,
+    /// When set, the attribute must also equal this value. Strings, numbers
+    /// and booleans can be compared.
+    #[serde(default)]
+    pub value: Option<serde_json::Value>,
+}
+
+/// A regular expression, compiled when the config is read.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "String")]
+pub struct NamePattern(Regex);
+
+impl TryFrom<String> for NamePattern {
+    type Error = regex::Error;
+
+    fn try_from(source: String) -> Result<Self, Self::Error> {
+        Regex::new(&source).map(NamePattern)
+    }
+}
+
+impl Selector {
+    pub fn matches(&self, dom: &WeakDom, instance: &Instance) -> bool {
+        match self {
+            Selector::Class(name) => class::is_a(&instance.class, name),
+            Selector::Tag(tag) => has_tag(instance, tag),
+            Selector::Attribute(selector) => selector.matches(instance),
+            Selector::Name(pattern) => glob_matches(pattern, &instance.name),
+            Selector::NameRegex(NamePattern(regex)) => regex.is_match(&instance.name),
+            Selector::Ancestor(pattern) => {
+                let mut parent = dom.get_by_ref(instance.parent());
+                while let Some(current) = parent {
+                    if current.referent() == dom.root_ref() {
+                        break;
+                    }
+                    if glob_matches(pattern, &current.name) {
+                        return true;
+                    }
+                    parent = dom.get_by_ref(current.parent());
+                }
+                false
+            }
+            Selector::All(selectors) => selectors.iter().all(|x| x.matches(dom, instance)),
+            Selector::Any(selectors) => selectors.iter().any(|x| x.matches(dom, instance)),
+            Selector::Not(selector) => !selector.matches(dom, instance),
+        }
+    }
+
+    /// Catches mistakes that would otherwise silently match nothing.
+    pub fn validate(&self) -> Result<(), RenderError> {
+        match self {
+            Selector::Class(name) if !class::is_known(name) => Err(RenderError::InvalidConfig(
+                format!("unknown class {} in selector", name),
+            )),
+            Selector::All(selectors) | Selector::Any(selectors) => {
+                selectors.iter().try_for_each(|x| x.validate())
+            }
+            Selector::Not(selector) => selector.validate(),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl AttributeSelector {
+    fn matches(&self, instance: &Instance) -> bool {
+        let attributes = match instance.properties.get("Attributes") {
+            Some(Variant::Attributes(v)) => v,
+            _ => return false,
+        };
+
+        let attribute = match attributes.get(self.name.as_str()) {
+            Some(v) => v,
+            None => return false,
+        };
+
+        let expected = match &self.value {
+            Some(v) => v,
+            None => return true,
+        };
+
+        match (attribute, expected) {
+            (Variant::String(a), serde_json::Value::String(b)) => a == b,
+            (Variant::Bool(a), serde_json::Value::Bool(b)) => a == b,
+            (Variant::Float64(a), serde_json::Value::Number(b)) => Some(*a) == b.as_f64(),
+            (Variant::Float32(a), serde_json::Value::Number(b)) => Some(*a as f64) == b.as_f64(),
+            (Variant::Int32(a), serde_json::Value::Number(b)) => Some(*a as i64) == b.as_i64(),
+            (Variant::Int64(a), serde_json::Value::Number(b)) => Some(*a) == b.as_i64(),
+            _ => false,
+        }
+    }
+}
+
+fn has_tag(instance: &Instance, tag: &str) -> bool {
+    match instance.properties.get("Tags") {
+        Some(Variant::Tags(tags)) => tags.iter().any(|x| x == tag),
+        // older files keep tags as a null separated string.
+        Some(Variant::BinaryString(tags)) => {
+            let tags: &[u8] = tags.as_ref();
+            tags.split(|&x| x == 0).any(|x| x == tag.as_bytes())
+        }
+        _ => false,
+    }
+}
+
+/// Matches a name against a glob pattern with `*` and `?` wildcards.
+fn glob_matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    // position to go back to after the last `*`, and how much of the name
+    // it has swallowed so far.
+    let mut backtrack: Option<(usize, usize)> = None;
+    let (mut p, mut n) = (0, 0);
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&x) if x == '?' || x == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, swallowed)) => {
+                    p = star + 1;
+                    n = swallowed + 1;
+                    backtrack = Some((star, swallowed + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&x| x == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use rbx_dom_weak::types::Ref;
+    use rbx_dom_weak::InstanceBuilder;
+    use rbx_types::{Attributes, Tags};
+
+    use super::*;
+
+    fn selector(json: &str) -> Selector {
+        serde_json::from_str(json).unwrap()
+    }
+
+    /// A place with `Workspace.Map.Road`, returning the road.
+    fn place(road: InstanceBuilder) -> (WeakDom, Ref) {
+        let mut dom = WeakDom::new(InstanceBuilder::new("DataModel"));
+        let workspace = dom.insert(dom.root_ref(), InstanceBuilder::new("Workspace"));
+        let map = dom.insert(workspace, InstanceBuilder::new("Folder").with_name("Map"));
+        let road = dom.insert(map, road);
+        (dom, road)
+    }
+
+    fn matches(json: &str, road: InstanceBuilder) -> bool {
+        let (dom, road) = place(road);
+        selector(json).matches(&dom, dom.get_by_ref(road).unwrap())
+    }
+
+    #[test]
+    fn glob_wildcards() {
+        assert!(glob_matches("Road", "Road"));
+        assert!(!glob_matches("Road", "Roads"));
+        assert!(glob_matches("Road*", "Roads"));
+        assert!(glob_matches("Road*", "Road"));
+        assert!(glob_matches("*Road", "MainRoad"));
+        assert!(glob_matches("R?ad", "Read"));
+        assert!(!glob_matches("R?ad", "Rad"));
+        assert!(glob_matches("*a*b*", "xaxxbx"));
+        assert!(!glob_matches("*a*b", "xaxxbx"));
+        assert!(glob_matches("*", ""));
+        assert!(!glob_matches("?", ""));
+    }
+
+    #[test]
+    fn class_follows_inheritance() {
+        let part = || InstanceBuilder::new("Part");
+        assert!(matches(r#"{"class": "Part"}"#, part()));
+        assert!(matches(r#"{"class": "BasePart"}"#, part()));
+        assert!(!matches(r#"{"class": "MeshPart"}"#, part()));
+        assert!(!matches(
+            r#"{"class": "BasePart"}"#,
+            InstanceBuilder::new("Model")
+        ));
+    }
+
+    #[test]
+    fn tags() {
+        let mut tags = Tags::new();
+        tags.push("Road");
+        tags.push("Lit");
+        let tagged = || InstanceBuilder::new("Part").with_property("Tags", tags.clone());
+
+        assert!(matches(r#"{"tag": "Lit"}"#, tagged()));
+        assert!(!matches(r#"{"tag": "Hidden"}"#, tagged()));
+        assert!(!matches(r#"{"tag": "Lit"}"#, InstanceBuilder::new("Part")));
+    }
+
+    #[test]
+    fn attribute_values() {
+        let attributes = Attributes::new()
+            .with("Kind", "Highway")
+            .with("Lanes", 4.0f64)
+            .with("Lit", true);
+        let road = || InstanceBuilder::new("Part").with_property("Attributes", attributes.clone());
+
+        assert!(matches(r#"{"attribute": {"name": "Kind"}}"#, road()));
+        assert!(matches(
+            r#"{"attribute": {"name": "Kind", "value": "Highway"}}"#,
+            road()
+        ));
+        assert!(!matches(
+            r#"{"attribute": {"name": "Kind", "value": "Street"}}"#,
+            road()
+        ));
+        assert!(matches(
+            r#"{"attribute": {"name": "Lanes", "value": 4}}"#,
+            road()
+        ));
+        assert!(matches(
+            r#"{"attribute": {"name": "Lit", "value": true}}"#,
+            road()
+        ));
+        // a value of the wrong type never matches.
+        assert!(!matches(
+            r#"{"attribute": {"name": "Lanes", "value": "4"}}"#,
+            road()
+        ));
+        assert!(!matches(r#"{"attribute": {"name": "Speed"}}"#, road()));
+        assert!(!matches(
+            r#"{"attribute": {"name": "Kind"}}"#,
+            InstanceBuilder::new("Part")
+        ));
+    }
+
+    #[test]
+    fn names_and_ancestors() {
+        let road = || InstanceBuilder::new("Part").with_name("MainRoad");
+        assert!(matches(r#"{"name": "*Road"}"#, road()));
+        assert!(matches(r#"{"name_regex": "^Main"}"#, road()));
+        assert!(!matches(r#"{"name_regex": "^Road"}"#, road()));
+        assert!(matches(r#"{"ancestor": "Ma?"}"#, road()));
+        assert!(!matches(r#"{"ancestor": "MainRoad"}"#, road()));
+    }
+
+    #[test]
+    fn combinators() {
+        let road = || InstanceBuilder::new("Part").with_name("Road");
+        assert!(matches(
+            r#"{"all": [{"class": "BasePart"}, {"name": "Road"}]}"#,
+            road()
+        ));
+        assert!(!matches(
+            r#"{"all": [{"class": "BasePart"}, {"name": "Path"}]}"#,
+            road()
+        ));
+        assert!(matches(
+            r#"{"any": [{"class": "Model"}, {"name": "Road"}]}"#,
+            road()
+        ));
+        assert!(!matches(
+            r#"{"any": [{"class": "Model"}, {"name": "Path"}]}"#,
+            road()
+        ));
+        assert!(matches(r#"{"not": {"class": "Model"}}"#, road()));
+        assert!(!matches(r#"{"not": {"name": "Road"}}"#, road()));
+        assert!(matches(r#"{"all": []}"#, road()));
+        assert!(!matches(r#"{"any": []}"#, road()));
+    }
+}
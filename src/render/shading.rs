@@ -0,0 +1,184 @@
+//! Tinting parts by how high they are, and shading them by which way their
+//! top faces.
+
+use serde::Deserialize;
+
+use super::error::RenderError;
+use super::scene::PartDrawData;
+use super::style::check_color;
+
+/// Used when a config doesn't give its own gradient, stretched over the
+/// heights of everything drawn: low ground is green, then brown, then white.
+const DEFAULT_GRADIENT: [(f32, [u8; 3]); 3] = [
+    (0f32, [70, 130, 70]),
+    (0.5f32, [170, 140, 90]),
+    (1f32, [245, 245, 245]),
+];
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct HeightShading {
+    /// Colors at given heights in studs, lowest first. Heights between two
+    /// stops blend between their colors.
+    #[serde(default)]
+    pub gradient: Vec<GradientStop>,
+    /// How much of the gradient color to mix into each part's own color,
+    /// from 0 to 1
+    #[serde(default = "default_strength")]
+    pub strength: f32,
+    /// Lights parts from one direction, so slopes facing away are darker
+    #[serde(default)]
+    pub hillshade: Option<Hillshade>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GradientStop {
+    pub height: f32,
+    pub color: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Hillshade {
+    /// Compass direction the light comes from in degrees, clockwise from
+    /// north (the top of the image)
+    #[serde(default = "default_azimuth")]
+    pub azimuth: f32,
+    /// Angle of the light above the horizon in degrees
+    #[serde(default = "default_altitude")]
+    pub altitude: f32,
+    /// How strong the shading is, from 0 to 1
+    #[serde(default = "default_strength")]
+    pub strength: f32,
+}
+
+fn default_strength() -> f32 {
+    1f32
+}
+
+fn default_azimuth() -> f32 {
+    315f32
+}
+
+fn default_altitude() -> f32 {
+    45f32
+}
+
+impl HeightShading {
+    pub fn validate(&self) -> Result<(), RenderError> {
+        for stop in self.gradient.iter() {
+            check_color(&stop.color, "gradient color", "height_shading")?;
+        }
+
+        if self
+            .gradient
+            .windows(2)
+            .any(|pair| pair[0].height > pair[1].height)
+        {
+            return Err(RenderError::InvalidConfig(
+                "height_shading gradient stops must go from lowest to highest".to_owned(),
+            ));
+        }
+
+        if !(0f32..=1f32).contains(&self.strength) {
+            return Err(RenderError::InvalidConfig(format!(
+                "height_shading strength must be between 0 and 1, found {}",
+                self.strength
+            )));
+        }
+
+        if let Some(hillshade) = &self.hillshade {
+            if !(0f32..=1f32).contains(&hillshade.strength)
+                || hillshade.altitude <= 0f32
+                || hillshade.altitude > 90f32
+            {
+                return Err(RenderError::InvalidConfig(
+                    "hillshade needs a strength between 0 and 1 and an altitude above 0 and up to 90"
+                        .to_owned(),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Recolors every part by the height of its top.
+    pub(crate) fn apply(&self, parts: &mut [PartDrawData]) {
+        let gradient: Vec<(f32, [u8; 3])> = if self.gradient.is_empty() {
+            let low = parts.iter().map(|x| x.top()).fold(f32::INFINITY, f32::min);
+            let high = parts
+                .iter()
+                .map(|x| x.top())
+                .fold(f32::NEG_INFINITY, f32::max);
+            DEFAULT_GRADIENT
+                .iter()
+                .map(|(t, color)| (low + (high - low) * t, *color))
+                .collect()
+        } else {
+            self.gradient
+                .iter()
+                .map(|stop| (stop.height, [stop.color[0], stop.color[1], stop.color[2]]))
+                .collect()
+        };
+
+        for part in parts.iter_mut() {
+            let target = sample(&gradient, part.top());
+            let mut rgb = [0f32; 3];
+            for (i, channel) in rgb.iter_mut().enumerate() {
+                let own = part.color[i] as f32;
+                *channel = own + (target[i] - own) * self.strength;
+            }
+
+            if let Some(hillshade) = &self.hillshade {
+                let light = hillshade.light(part);
+                for channel in rgb.iter_mut() {
+                    *channel *= light;
+                }
+            }
+
+            for (i, channel) in rgb.iter().enumerate() {
+                part.color[i] = channel.round().clamp(0f32, 255f32) as u8;
+            }
+        }
+    }
+}
+
+impl Hillshade {
+    /// How much brighter or darker a part is than flat ground would be.
+    fn light(&self, part: &PartDrawData) -> f32 {
+        let azimuth = self.azimuth.to_radians();
+        let altitude = self.altitude.to_radians();
+
+        // north is -Z, so light from the north shines towards +Z.
+        let light = (
+            altitude.cos() * azimuth.sin(),
+            altitude.sin(),
+            -altitude.cos() * azimuth.cos(),
+        );
+        let normal = part.top_normal();
+        let lit = (normal.x * light.0 + normal.y * light.1 + normal.z * light.2).max(0f32);
+
+        1f32 + (lit / light.1 - 1f32) * self.strength
+    }
+}
+
+/// Finds the color of a gradient at `height`, holding the end colors past
+/// either end.
+fn sample(gradient: &[(f32, [u8; 3])], height: f32) -> [f32; 3] {
+    let to_f32 = |color: [u8; 3]| [color[0] as f32, color[1] as f32, color[2] as f32];
+
+    let upper = gradient.iter().position(|(stop, _)| *stop >= height);
+    match upper {
+        Some(0) => to_f32(gradient[0].1),
+        None => to_f32(gradient[gradient.len() - 1].1),
+        Some(i) => {
+            let (low_height, low) = gradient[i - 1];
+            let (high_height, high) = gradient[i];
+            let t = (height - low_height) / (high_height - low_height).max(f32::EPSILON);
+            let (low, high) = (to_f32(low), to_f32(high));
+            [
+                low[0] + (high[0] - low[0]) * t,
+                low[1] + (high[1] - low[1]) * t,
+                low[2] + (high[2] - low[2]) * t,
+            ]
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use rbx_dom_weak::WeakDom;
+
+use super::error::RenderError;
+use super::progress::{NoProgress, Progress, ProgressReader, Stage};
+
+/// The different file types we support
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    /// An XML place file
+    Rbxlx,
+    /// A binary place file
+    Rbxl,
+    /// An XML model file
+    Rbxmx,
+    /// A binary model file
+    Rbxm,
+}
+
+impl FileKind {
+    pub fn from_path(path: &Path) -> Option<FileKind> {
+        let extension = path.extension()?.to_str()?;
+
+        match extension {
+            "rbxlx" => Some(FileKind::Rbxlx),
+            "rbxl" => Some(FileKind::Rbxl),
+            "rbxmx" => Some(FileKind::Rbxmx),
+            "rbxm" => Some(FileKind::Rbxm),
+            _ => None,
+        }
+    }
+
+    /// Whether this is a model file rather than a full place.
+    pub fn is_model(self) -> bool {
+        matches!(self, FileKind::Rbxmx | FileKind::Rbxm)
+    }
+}
+
+/// Decodes a place or model file, working out its kind from the extension.
+pub fn load_dom(path: &Path) -> Result<(WeakDom, FileKind), RenderError> {
+    load_dom_with_progress(path, &NoProgress)
+}
+
+/// Decodes a place or model file like [`load_dom`], reporting how much of
+/// the file has been read as it goes.
+pub fn load_dom_with_progress(
+    path: &Path,
+    progress: &dyn Progress,
+) -> Result<(WeakDom, FileKind), RenderError> {
+    let kind =
+        FileKind::from_path(path).ok_or_else(|| RenderError::UnknownFileKind(path.to_owned()))?;
+
+    let file_read_error = |source| RenderError::FileRead {
+        path: path.to_owned(),
+        source,
+    };
+    let file = File::open(path).map_err(file_read_error)?;
+    let size = file.metadata().map_err(file_read_error)?.len();
+
+    progress.start(Stage::Decoding, Some(size));
+    let file_reader = BufReader::new(ProgressReader {
+        inner: file,
+        progress,
+    });
+
+    let dom = match kind {
+        FileKind::Rbxl | FileKind::Rbxm => rbx_binary::from_reader(file_reader)?,
+        FileKind::Rbxlx | FileKind::Rbxmx => rbx_xml::from_reader_default(file_reader)?,
+    };
+    progress.finish();
+
+    Ok((dom, kind))
+}
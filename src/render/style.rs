@@ -0,0 +1,234 @@
+use serde::Deserialize;
+use tiny_skia::{
+    FilterQuality, Paint, Pattern, Pixmap, Shader, SpreadMode, Stroke, StrokeDash, Transform,
+};
+
+use super::error::RenderError;
+use super::view::View;
+
+/// How the parts picked by a rule are painted, on top of their color.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PartStyle {
+    /// Multiplies the alpha of the fill, from 0 to 1. Use 0 to only draw the
+    /// outline or hatching.
+    #[serde(default)]
+    pub fill_opacity: Option<f32>,
+    /// Outline drawn around each part
+    #[serde(default)]
+    pub stroke: Option<StrokeStyle>,
+    /// Parallel lines drawn across each part
+    #[serde(default)]
+    pub hatch: Option<HatchStyle>,
+}
+
+/// Changes made to a part's color before it is drawn.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ColorAdjustment {
+    /// Blends the color towards this one, using its alpha as the strength
+    #[serde(default)]
+    pub tint: Option<Vec<u8>>,
+    /// Darkens the color, from 0 (unchanged) to 1 (black)
+    #[serde(default)]
+    pub darken: Option<f32>,
+    /// Replaces the alpha of the color
+    #[serde(default)]
+    pub alpha: Option<u8>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct StrokeStyle {
+    pub color: Vec<u8>,
+    /// Line width in pixels
+    #[serde(default = "default_line_width")]
+    pub width: f32,
+    /// Alternating lengths of dashes and gaps in pixels, eg. `[6, 4]`.
+    /// Solid when empty.
+    #[serde(default)]
+    pub dash: Vec<f32>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct HatchStyle {
+    /// Color of the lines, defaults to the color of the part
+    #[serde(default)]
+    pub color: Option<Vec<u8>>,
+    /// Distance between lines in pixels
+    #[serde(default = "default_hatch_spacing")]
+    pub spacing: f32,
+    /// Line width in pixels
+    #[serde(default = "default_line_width")]
+    pub width: f32,
+    /// Clockwise angle of the lines in degrees, 0 being horizontal
+    #[serde(default = "default_hatch_angle")]
+    pub angle: f32,
+}
+
+fn default_line_width() -> f32 {
+    1f32
+}
+
+fn default_hatch_spacing() -> f32 {
+    8f32
+}
+
+fn default_hatch_angle() -> f32 {
+    45f32
+}
+
+impl PartStyle {
+    /// Scales the alpha of a fill color by `fill_opacity`.
+    pub(crate) fn fill_alpha(&self, alpha: u8) -> u8 {
+        match self.fill_opacity {
+            Some(opacity) => (alpha as f32 * opacity).round() as u8,
+            None => alpha,
+        }
+    }
+
+    pub fn validate(&self, rule: &str) -> Result<(), RenderError> {
+        if let Some(opacity) = self.fill_opacity {
+            if !(0f32..=1f32).contains(&opacity) {
+                return Err(RenderError::InvalidConfig(format!(
+                    "fill_opacity for {} must be between 0 and 1, found {}",
+                    rule, opacity
+                )));
+            }
+        }
+
+        if let Some(stroke) = &self.stroke {
+            check_color(&stroke.color, "stroke color", rule)?;
+
+            if stroke.width < 0f32 {
+                return Err(RenderError::InvalidConfig(format!(
+                    "stroke width for {} can't be negative",
+                    rule
+                )));
+            }
+
+            if !stroke.dash.is_empty() && stroke.to_dash().is_none() {
+                return Err(RenderError::InvalidConfig(format!(
+                    "stroke dash for {} needs an even number of lengths that aren't all 0",
+                    rule
+                )));
+            }
+        }
+
+        if let Some(hatch) = &self.hatch {
+            if let Some(color) = &hatch.color {
+                check_color(color, "hatch color", rule)?;
+            }
+
+            if hatch.spacing < 1f32 || hatch.width <= 0f32 || hatch.width > hatch.spacing {
+                return Err(RenderError::InvalidConfig(format!(
+                    "hatch for {} needs a spacing of at least 1 and a width between 0 and the spacing",
+                    rule
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl ColorAdjustment {
+    pub(crate) fn apply(&self, color: &[u8]) -> Vec<u8> {
+        let mut rgb = [color[0] as f32, color[1] as f32, color[2] as f32];
+
+        if let Some(tint) = &self.tint {
+            let strength = tint[3] as f32 / 255f32;
+            for (channel, target) in rgb.iter_mut().zip(tint.iter()) {
+                *channel += (*target as f32 - *channel) * strength;
+            }
+        }
+
+        if let Some(darken) = self.darken {
+            for channel in rgb.iter_mut() {
+                *channel *= 1f32 - darken;
+            }
+        }
+
+        vec![
+            rgb[0].round() as u8,
+            rgb[1].round() as u8,
+            rgb[2].round() as u8,
+            self.alpha.unwrap_or(color[3]),
+        ]
+    }
+
+    pub fn validate(&self, rule: &str) -> Result<(), RenderError> {
+        if let Some(tint) = &self.tint {
+            check_color(tint, "tint", rule)?;
+        }
+
+        if let Some(darken) = self.darken {
+            if !(0f32..=1f32).contains(&darken) {
+                return Err(RenderError::InvalidConfig(format!(
+                    "darken for {} must be between 0 and 1, found {}",
+                    rule, darken
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl StrokeStyle {
+    pub(crate) fn to_stroke(&self) -> Stroke {
+        Stroke {
+            width: self.width,
+            dash: self.to_dash(),
+            ..Stroke::default()
+        }
+    }
+
+    fn to_dash(&self) -> Option<StrokeDash> {
+        if self.dash.iter().all(|&x| x == 0f32) {
+            return None;
+        }
+
+        StrokeDash::new(self.dash.clone(), 0f32)
+    }
+}
+
+impl HatchStyle {
+    /// Draws a single horizontal line into a square tile, which the pattern
+    /// shader repeats and rotates.
+    pub(crate) fn tile(&self, color: &[u8]) -> Option<Pixmap> {
+        let size = self.spacing.round().max(1f32) as u32;
+        let mut tile = Pixmap::new(size, size)?;
+
+        let mut paint = Paint::default();
+        paint.set_color_rgba8(color[0], color[1], color[2], color[3]);
+        let rect = tiny_skia::Rect::from_xywh(0f32, 0f32, size as f32, self.width)?;
+        tile.fill_rect(rect, &paint, Transform::identity(), None);
+
+        Some(tile)
+    }
+
+    /// A shader that repeats `tile` at this hatch's angle.
+    ///
+    /// The lines are pinned to the world origin under `view`, so they stay
+    /// put wherever a part is cut off, eg. at the edge of a map tile.
+    pub(crate) fn shader<'a>(&self, tile: &'a Pixmap, view: View) -> Shader<'a> {
+        Pattern::new(
+            tile.as_ref(),
+            SpreadMode::Repeat,
+            FilterQuality::Bilinear,
+            1f32,
+            Transform::from_rotate(self.angle).post_translate(view.offset_x, view.offset_z),
+        )
+    }
+}
+
+pub(crate) fn check_color(color: &[u8], what: &str, rule: &str) -> Result<(), RenderError> {
+    if color.len() != 4 {
+        return Err(RenderError::InvalidConfig(format!(
+            "{} for {} must have 4 values (RGBA), found {}",
+            what,
+            rule,
+            color.len()
+        )));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,230 @@
+//! Cutting a render into a pyramid of fixed size map tiles.
+//!
+//! Tiles follow the usual slippy map layout used by web map viewers: zoom
+//! level `z` splits the map into `2^z` by `2^z` tiles, numbered from the
+//! top left, so tile `{z}/{x}/{y}` has X growing east and Y growing south.
+//! World X runs along the tile X axis and world Z along the tile Y axis.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use rayon::prelude::*;
+use tiny_skia::{Pixmap, Transform};
+
+use super::error::RenderError;
+use super::progress::{Progress, Stage};
+use super::scene::{self, PartDrawData, TerrainLayer};
+use super::view::View;
+use crate::math_lib::bounds::Bounds;
+
+/// Deeper zoom levels would number tiles past what fits in a `u32`, and are
+/// far more detail than a place has anyway.
+pub(crate) const MAX_ZOOM: u8 = 24;
+
+/// The size of each tile and which zoom levels to render.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TileParams {
+    /// Width and height of every tile in pixels
+    pub tile_size: u32,
+    pub min_zoom: u8,
+    pub max_zoom: u8,
+}
+
+impl TileParams {
+    pub fn validate(&self) -> Result<(), RenderError> {
+        if self.tile_size == 0 {
+            return Err(RenderError::InvalidImageSize {
+                width: self.tile_size,
+                height: self.tile_size,
+            });
+        }
+
+        if self.min_zoom > self.max_zoom || self.max_zoom > MAX_ZOOM {
+            return Err(RenderError::InvalidZoomRange {
+                min_zoom: self.min_zoom,
+                max_zoom: self.max_zoom,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for TileParams {
+    fn default() -> Self {
+        TileParams {
+            tile_size: 256,
+            min_zoom: 0,
+            max_zoom: 5,
+        }
+    }
+}
+
+/// The position of a tile in the pyramid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TileId {
+    pub z: u8,
+    pub x: u32,
+    pub y: u32,
+}
+
+impl TileId {
+    /// The path of the tile relative to the root of the pyramid, in the
+    /// `{z}/{x}/{y}.{extension}` layout.
+    pub fn path(&self, extension: &str) -> PathBuf {
+        [
+            self.z.to_string(),
+            self.x.to_string(),
+            format!("{}.{}", self.y, extension),
+        ]
+        .iter()
+        .collect()
+    }
+
+    fn children(&self) -> [TileId; 4] {
+        let (z, x, y) = (self.z + 1, self.x * 2, self.y * 2);
+        [
+            TileId { z, x, y },
+            TileId { z, x: x + 1, y },
+            TileId { z, x, y: y + 1 },
+            TileId {
+                z,
+                x: x + 1,
+                y: y + 1,
+            },
+        ]
+    }
+}
+
+/// Everything selected for a render, ready to be drawn tile by tile.
+pub struct TilePyramid {
+    parts: Vec<PartDrawData>,
+    terrain: Option<TerrainLayer>,
+    params: TileParams,
+    /// Top left corner of the single tile at zoom 0, in studs
+    origin_x: f32,
+    origin_z: f32,
+    /// Width and depth of the tile at zoom 0, in studs
+    extent: f32,
+    progress: Arc<dyn Progress>,
+}
+
+impl TilePyramid {
+    /// Centers a square around `bounds` to act as the tile at zoom 0, so
+    /// the world isn't stretched to fit.
+    pub(crate) fn new(
+        parts: Vec<PartDrawData>,
+        terrain: Option<TerrainLayer>,
+        bounds: &Bounds,
+        params: TileParams,
+        progress: Arc<dyn Progress>,
+    ) -> TilePyramid {
+        let extent = bounds.width().max(bounds.depth()).max(f32::EPSILON);
+        let (center_x, center_z) = bounds.center();
+
+        TilePyramid {
+            parts,
+            terrain,
+            params,
+            origin_x: center_x - extent * 0.5f32,
+            origin_z: center_z - extent * 0.5f32,
+            extent,
+            progress,
+        }
+    }
+
+    pub fn params(&self) -> TileParams {
+        self.params
+    }
+
+    /// The area of the world covered by a tile.
+    pub fn tile_bounds(&self, id: TileId) -> Bounds {
+        let size = self.extent / (1u64 << id.z) as f32;
+        let min_x = self.origin_x + id.x as f32 * size;
+        let min_z = self.origin_z + id.y as f32 * size;
+        Bounds::new(min_x, min_z, min_x + size, min_z + size)
+    }
+
+    /// Draws every tile that has something in it, handing each one to
+    /// `write` as soon as it is done, and returns how many were written.
+    ///
+    /// Empty tiles are left out, and so is everything under them, so large
+    /// empty stretches of a map cost nothing at deep zoom levels. Tiles are
+    /// drawn in parallel, so `write` can be called from several threads at
+    /// once and in any order.
+    pub fn for_each_tile<E, F>(&self, write: F) -> Result<usize, E>
+    where
+        E: Send,
+        F: Fn(TileId, Pixmap) -> Result<(), E> + Sync,
+    {
+        let candidates: Vec<&PartDrawData> = self.parts.iter().collect();
+
+        // empty tiles aren't known about until their parent is drawn, so
+        // there is no total to give.
+        self.progress.start(Stage::DrawingTiles, None);
+        let written = self.visit(TileId { z: 0, x: 0, y: 0 }, &candidates, &write);
+        self.progress.finish();
+        written
+    }
+
+    /// Draws a tile and then its children, only looking at the parts that
+    /// touched its parent.
+    fn visit<E, F>(&self, id: TileId, candidates: &[&PartDrawData], write: &F) -> Result<usize, E>
+    where
+        E: Send,
+        F: Fn(TileId, Pixmap) -> Result<(), E> + Sync,
+    {
+        let bounds = self.tile_bounds(id);
+        let scale = self.params.tile_size as f32 / bounds.width();
+
+        // outlines reach past a part's footprint by a set number of pixels,
+        // which is less of the world at every zoom, so anything touching a
+        // child also touched its parent.
+        let parts: Vec<&PartDrawData> = candidates
+            .iter()
+            .copied()
+            .filter(|part| part.touches(&bounds, scale))
+            .collect();
+        let terrain = self
+            .terrain
+            .as_ref()
+            .filter(|terrain| terrain.bounds().intersects(&bounds));
+
+        if parts.is_empty() && terrain.is_none() {
+            return Ok(0);
+        }
+
+        let mut written = 0;
+        if id.z >= self.params.min_zoom {
+            let view = View {
+                offset_x: -bounds.min_x * scale,
+                offset_z: -bounds.min_z * scale,
+                scale,
+            };
+
+            // the size is checked when the pyramid is built.
+            let mut pixmap = Pixmap::new(self.params.tile_size, self.params.tile_size).unwrap();
+            scene::draw_layers(
+                &mut pixmap.as_mut(),
+                terrain,
+                parts.iter().copied(),
+                view,
+                Transform::identity(),
+            );
+            write(id, pixmap)?;
+            self.progress.advance(1);
+            written += 1;
+        }
+
+        if id.z < self.params.max_zoom {
+            let children = id
+                .children()
+                .par_iter()
+                .map(|child| self.visit(*child, &parts, write))
+                .collect::<Result<Vec<usize>, E>>()?;
+            written += children.iter().sum::<usize>();
+        }
+
+        Ok(written)
+    }
+}
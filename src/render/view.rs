@@ -0,0 +1,70 @@
+use crate::math_lib::bounds::Bounds;
+
+/// How the world is placed in the image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Framing {
+    /// Place the world by hand, a world position lands on pixel
+    /// `position * scale + offset`.
+    Manual {
+        offset_x: f32,
+        offset_z: f32,
+        scale: f32,
+    },
+    /// Fit everything that is drawn inside the image, leaving `margin`
+    /// pixels free on every side.
+    Fit { margin: f32 },
+}
+
+/// The size of the image to render, and how the world is placed in it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ViewParams {
+    pub width: u32,
+    pub height: u32,
+    pub framing: Framing,
+}
+
+/// Maps world space X/Z coordinates onto pixel coordinates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct View {
+    pub offset_x: f32,
+    pub offset_z: f32,
+    pub scale: f32,
+}
+
+impl View {
+    /// Builds a view that fits `bounds` inside an image of the given size,
+    /// leaving `margin` pixels free on every side.
+    pub fn fit(bounds: &Bounds, width: f32, height: f32, margin: f32) -> View {
+        let usable_width = (width - 2f32 * margin).max(1f32);
+        let usable_height = (height - 2f32 * margin).max(1f32);
+
+        // guard against flat selections, eg. a single part seen edge on.
+        let scale = (usable_width / bounds.width().max(f32::EPSILON))
+            .min(usable_height / bounds.depth().max(f32::EPSILON));
+
+        let (center_x, center_z) = bounds.center();
+        View {
+            offset_x: width * 0.5f32 - center_x * scale,
+            offset_z: height * 0.5f32 - center_z * scale,
+            scale,
+        }
+    }
+
+    /// Converts a world space X/Z position into pixel coordinates.
+    pub fn project(self, x: f32, z: f32) -> (f32, f32) {
+        (
+            x * self.scale + self.offset_x,
+            z * self.scale + self.offset_z,
+        )
+    }
+
+    /// The area of the world covered by an image of the given size.
+    pub fn to_world(self, width: f32, height: f32) -> Bounds {
+        Bounds::new(
+            -self.offset_x / self.scale,
+            -self.offset_z / self.scale,
+            (width - self.offset_x) / self.scale,
+            (height - self.offset_z) / self.scale,
+        )
+    }
+}
@@ -0,0 +1,69 @@
+/// Names of the terrain materials, indexed by the id SmoothGrid stores for
+/// them. Id 0 is air.
+const MATERIAL_NAMES: [&str; 23] = [
+    "Air",
+    "Water",
+    "Grass",
+    "Slate",
+    "Concrete",
+    "Brick",
+    "Sand",
+    "WoodPlanks",
+    "Rock",
+    "Glacier",
+    "Snow",
+    "Sandstone",
+    "Mud",
+    "Basalt",
+    "Ground",
+    "CrackedLava",
+    "Asphalt",
+    "Cobblestone",
+    "Ice",
+    "LeafyGrass",
+    "Salt",
+    "Limestone",
+    "Pavement",
+];
+
+/// Studio's default colors for each terrain material, in the same order as
+/// `MATERIAL_NAMES`.
+const DEFAULT_COLORS: [[u8; 4]; 23] = [
+    [0, 0, 0, 0],
+    [12, 84, 91, 255],
+    [106, 127, 63, 255],
+    [63, 127, 107, 255],
+    [127, 102, 63, 255],
+    [138, 86, 62, 255],
+    [143, 126, 95, 255],
+    [139, 109, 79, 255],
+    [102, 108, 111, 255],
+    [101, 176, 234, 255],
+    [195, 199, 218, 255],
+    [137, 90, 71, 255],
+    [58, 46, 36, 255],
+    [30, 30, 37, 255],
+    [102, 92, 59, 255],
+    [232, 156, 74, 255],
+    [115, 123, 107, 255],
+    [132, 123, 90, 255],
+    [129, 194, 224, 255],
+    [115, 132, 74, 255],
+    [198, 189, 181, 255],
+    [206, 173, 148, 255],
+    [148, 148, 140, 255],
+];
+
+/// The id SmoothGrid uses for empty space.
+pub const AIR: u8 = 0;
+
+pub fn material_name(id: u8) -> Option<&'static str> {
+    MATERIAL_NAMES.get(id as usize).copied()
+}
+
+pub fn default_color(id: u8) -> [u8; 4] {
+    DEFAULT_COLORS
+        .get(id as usize)
+        .copied()
+        .unwrap_or([255, 0, 255, 255])
+}
@@ -0,0 +1,410 @@
+//! Decoding for the `Terrain.SmoothGrid` property.
+//!
+//! SmoothGrid is stored as a run length encoded list of voxel chunks:
+//!
+//! * A header of two bytes, the format version (always 1) followed by the
+//!   log2 of the chunk size in voxels along each axis.
+//! * Chunks follow until the end of the data. Each chunk starts with a byte
+//!   holding two bits per axis (X in the lowest bits, then Y, then Z) that
+//!   give the width of that axis' position delta: 0, 1, 2 or 4 bytes. The
+//!   deltas are little endian signed integers, relative to the position of
+//!   the previous chunk, measured in chunks.
+//! * The voxels of the chunk follow as runs, with X varying fastest, then
+//!   Z, then Y. Each run starts with a flag byte: the low 6 bits are the
+//!   material, bit 6 means an occupancy byte follows (otherwise the voxel is
+//!   full) and bit 7 means a count byte follows (the run is count + 1
+//!   voxels long, otherwise 1).
+//!
+//! Only the top-most solid voxel of every column is kept, which is all a top
+//! down render needs.
+
+use std::collections::HashMap;
+
+use thiserror::Error;
+use tiny_skia::{ColorU8, Pixmap};
+
+use super::material;
+
+/// The size of a single voxel in studs.
+pub const VOXEL_SIZE: f32 = 4f32;
+
+const SUPPORTED_VERSION: u8 = 1;
+
+const OCCUPANCY_FLAG: u8 = 0b0100_0000;
+const COUNT_FLAG: u8 = 0b1000_0000;
+const MATERIAL_MASK: u8 = 0b0011_1111;
+
+#[derive(Debug, Error)]
+pub enum SmoothGridError {
+    #[error("SmoothGrid data ended unexpectedly")]
+    UnexpectedEof,
+
+    #[error("Unsupported SmoothGrid version {0}")]
+    UnsupportedVersion(u8),
+
+    #[error("Invalid SmoothGrid chunk size exponent {0}")]
+    InvalidChunkSize(u8),
+
+    #[error("SmoothGrid voxel runs overflow their chunk")]
+    RunOverflow,
+}
+
+/// The top-most solid voxel of a column of terrain.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TerrainColumn {
+    pub material: u8,
+    /// Height of the top of the surface in studs.
+    pub height: f32,
+}
+
+/// A top down view of terrain, one entry per column of voxels.
+#[derive(Debug, Clone)]
+pub struct TerrainMap {
+    /// X of the first column, in voxels.
+    pub min_x: i32,
+    /// Z of the first column, in voxels.
+    pub min_z: i32,
+    pub width: u32,
+    pub depth: u32,
+    columns: Vec<Option<TerrainColumn>>,
+}
+
+impl TerrainMap {
+    pub fn column(&self, x: u32, z: u32) -> Option<TerrainColumn> {
+        if x >= self.width || z >= self.depth {
+            return None;
+        }
+
+        self.columns[(z * self.width + x) as usize]
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.depth == 0
+    }
+
+    /// The lowest and highest surface in the map, in studs.
+    pub fn height_range(&self) -> Option<(f32, f32)> {
+        self.columns.iter().flatten().fold(None, |range, column| {
+            Some(match range {
+                None => (column.height, column.height),
+                Some((low, high)) => (low.min(column.height), high.max(column.height)),
+            })
+        })
+    }
+
+    /// Removes every column whose surface doesn't pass `keep`.
+    pub fn retain<F>(&mut self, keep: F)
+    where
+        F: Fn(&TerrainColumn) -> bool,
+    {
+        for column in self.columns.iter_mut() {
+            if let Some(top) = column {
+                if !keep(top) {
+                    *column = None;
+                }
+            }
+        }
+    }
+
+    /// Draws the map with one pixel per column, coloring each column by its
+    /// material and shading it by its height.
+    ///
+    /// Pixel (0, 0) covers the voxel column at `min_x`, `min_z`.
+    pub fn to_pixmap<F>(&self, color_of: F) -> Option<Pixmap>
+    where
+        F: Fn(u8) -> [u8; 4],
+    {
+        let mut pixmap = Pixmap::new(self.width, self.depth)?;
+        let (low, high) = self.height_range()?;
+        let range = (high - low).max(f32::EPSILON);
+
+        for (pixel, column) in pixmap.pixels_mut().iter_mut().zip(self.columns.iter()) {
+            if let Some(column) = column {
+                let color = color_of(column.material);
+                // lower ground is drawn slightly darker so hills read from
+                // above.
+                let shade = 0.7f32 + 0.3f32 * (column.height - low) / range;
+                *pixel = ColorU8::from_rgba(
+                    (color[0] as f32 * shade).round() as u8,
+                    (color[1] as f32 * shade).round() as u8,
+                    (color[2] as f32 * shade).round() as u8,
+                    color[3],
+                )
+                .premultiply();
+            }
+        }
+
+        Some(pixmap)
+    }
+}
+
+/// Decodes SmoothGrid data into a top down map of its surface.
+pub fn decode_top_down(data: &[u8]) -> Result<TerrainMap, SmoothGridError> {
+    let mut reader = Reader { data, position: 0 };
+
+    let version = reader.read_u8()?;
+    if version != SUPPORTED_VERSION {
+        return Err(SmoothGridError::UnsupportedVersion(version));
+    }
+
+    let chunk_exponent = reader.read_u8()?;
+    if chunk_exponent > 8 {
+        return Err(SmoothGridError::InvalidChunkSize(chunk_exponent));
+    }
+    let chunk_size = 1i32 << chunk_exponent;
+    let voxels_per_chunk = (chunk_size * chunk_size * chunk_size) as usize;
+
+    let mut tops: HashMap<(i32, i32), (i32, TerrainColumn)> = HashMap::new();
+    let mut chunk_position = (0i32, 0i32, 0i32);
+
+    while !reader.is_done() {
+        let widths = reader.read_u8()?;
+        chunk_position.0 += reader.read_delta(widths & 0b11)?;
+        chunk_position.1 += reader.read_delta((widths >> 2) & 0b11)?;
+        chunk_position.2 += reader.read_delta((widths >> 4) & 0b11)?;
+
+        let mut index = 0usize;
+        while index < voxels_per_chunk {
+            let flags = reader.read_u8()?;
+            let material = flags & MATERIAL_MASK;
+            let occupancy = if flags & OCCUPANCY_FLAG != 0 {
+                reader.read_u8()?
+            } else {
+                u8::MAX
+            };
+            let count = if flags & COUNT_FLAG != 0 {
+                reader.read_u8()? as usize + 1
+            } else {
+                1
+            };
+
+            if index + count > voxels_per_chunk {
+                return Err(SmoothGridError::RunOverflow);
+            }
+
+            if material != material::AIR && occupancy > 0 {
+                for voxel in index..index + count {
+                    let voxel = voxel as i32;
+                    let x = chunk_position.0 * chunk_size + voxel % chunk_size;
+                    let z = chunk_position.2 * chunk_size + (voxel / chunk_size) % chunk_size;
+                    let y = chunk_position.1 * chunk_size + voxel / (chunk_size * chunk_size);
+
+                    let column = TerrainColumn {
+                        material,
+                        height: (y as f32 + occupancy as f32 / u8::MAX as f32) * VOXEL_SIZE,
+                    };
+
+                    tops.entry((x, z))
+                        .and_modify(|top| {
+                            if y > top.0 {
+                                *top = (y, column);
+                            }
+                        })
+                        .or_insert((y, column));
+                }
+            }
+
+            index += count;
+        }
+    }
+
+    Ok(build_map(tops))
+}
+
+fn build_map(tops: HashMap<(i32, i32), (i32, TerrainColumn)>) -> TerrainMap {
+    let min_x = tops.keys().map(|key| key.0).min().unwrap_or(0);
+    let min_z = tops.keys().map(|key| key.1).min().unwrap_or(0);
+    let max_x = tops.keys().map(|key| key.0).max().unwrap_or(-1);
+    let max_z = tops.keys().map(|key| key.1).max().unwrap_or(-1);
+
+    let width = (max_x - min_x + 1).max(0) as u32;
+    let depth = (max_z - min_z + 1).max(0) as u32;
+
+    let mut columns = vec![None; (width * depth) as usize];
+    for ((x, z), (_, column)) in tops {
+        let index = ((z - min_z) as u32 * width + (x - min_x) as u32) as usize;
+        columns[index] = Some(column);
+    }
+
+    TerrainMap {
+        min_x,
+        min_z,
+        width,
+        depth,
+        columns,
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn is_done(&self) -> bool {
+        self.position >= self.data.len()
+    }
+
+    fn read_u8(&mut self) -> Result<u8, SmoothGridError> {
+        let value = *self
+            .data
+            .get(self.position)
+            .ok_or(SmoothGridError::UnexpectedEof)?;
+        self.position += 1;
+        Ok(value)
+    }
+
+    /// Reads a signed little endian delta whose width is given by a two
+    /// bit code.
+    fn read_delta(&mut self, code: u8) -> Result<i32, SmoothGridError> {
+        let width = match code {
+            0 => return Ok(0),
+            1 => 1,
+            2 => 2,
+            _ => 4,
+        };
+
+        let bytes = self
+            .data
+            .get(self.position..self.position + width)
+            .ok_or(SmoothGridError::UnexpectedEof)?;
+        self.position += width;
+
+        Ok(match width {
+            1 => bytes[0] as i8 as i32,
+            2 => i16::from_le_bytes([bytes[0], bytes[1]]) as i32,
+            _ => i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GRASS: u8 = 2;
+    const SAND: u8 = 6;
+    const ROCK: u8 = 8;
+
+    /// Chunks of 2x2x2 voxels, where voxel indices run X first, then Z, then
+    /// Y.
+    #[rustfmt::skip]
+    fn sample() -> Vec<u8> {
+        vec![
+            // version 1, chunks of 2^1 voxels along each axis
+            1, 1,
+            // chunk at (0, 0, 0)
+            0b00_00_00,
+            // the bottom layer is grass
+            GRASS | COUNT_FLAG, 3,
+            // half full sand above (0, 0)
+            SAND | OCCUPANCY_FLAG, 128,
+            // air for the rest
+            COUNT_FLAG, 2,
+            // chunk at (-1, 0, 2), X moved by a 1 byte delta and Z by 2 bytes
+            0b10_00_01, 0xFF, 2, 0,
+            // rock at voxel (1, 0, 0) of the chunk
+            0, ROCK, COUNT_FLAG, 5,
+            // chunk at (-1, 1, 2), Y moved by a 1 byte delta
+            0b00_01_00, 1,
+            // rock above the last one, but with nothing in it
+            0, ROCK | OCCUPANCY_FLAG, 0, COUNT_FLAG, 5,
+        ]
+    }
+
+    #[test]
+    fn decodes_the_top_of_every_column() {
+        let map = decode_top_down(&sample()).unwrap();
+        assert_eq!((map.min_x, map.min_z), (-1, 0));
+        assert_eq!((map.width, map.depth), (3, 5));
+
+        let grass = TerrainColumn {
+            material: GRASS,
+            height: VOXEL_SIZE,
+        };
+        // the sand sits on the grass and is only half full.
+        let sand = TerrainColumn {
+            material: SAND,
+            height: (1f32 + 128f32 / 255f32) * VOXEL_SIZE,
+        };
+        let rock = TerrainColumn {
+            material: ROCK,
+            height: VOXEL_SIZE,
+        };
+
+        // map columns are offset by `min_x` and `min_z`.
+        assert_eq!(map.column(1, 0), Some(sand));
+        assert_eq!(map.column(2, 0), Some(grass));
+        assert_eq!(map.column(1, 1), Some(grass));
+        assert_eq!(map.column(2, 1), Some(grass));
+        assert_eq!(map.column(0, 4), Some(rock));
+
+        let filled = (0..map.depth)
+            .flat_map(|z| (0..map.width).map(move |x| (x, z)))
+            .filter(|&(x, z)| map.column(x, z).is_some())
+            .count();
+        assert_eq!(filled, 5);
+        assert_eq!(map.height_range(), Some((VOXEL_SIZE, sand.height)));
+    }
+
+    #[test]
+    fn decodes_four_byte_deltas() {
+        let data = [
+            1, 0, // chunks of a single voxel
+            0b11_00_11, 0x00, 0x00, 0x01, 0x00, 0x9C, 0xFF, 0xFF, 0xFF, GRASS,
+        ];
+        let map = decode_top_down(&data).unwrap();
+        assert_eq!((map.min_x, map.min_z), (65536, -100));
+        assert_eq!((map.width, map.depth), (1, 1));
+    }
+
+    #[test]
+    fn empty_grid() {
+        let map = decode_top_down(&[1, 4]).unwrap();
+        assert!(map.is_empty());
+        assert_eq!(map.height_range(), None);
+    }
+
+    #[test]
+    fn rejects_bad_headers() {
+        assert!(matches!(
+            decode_top_down(&[1]),
+            Err(SmoothGridError::UnexpectedEof)
+        ));
+        assert!(matches!(
+            decode_top_down(&[2, 4]),
+            Err(SmoothGridError::UnsupportedVersion(2))
+        ));
+        assert!(matches!(
+            decode_top_down(&[1, 9]),
+            Err(SmoothGridError::InvalidChunkSize(9))
+        ));
+    }
+
+    #[test]
+    fn rejects_truncated_data() {
+        let sample = sample();
+        for end in [3, 4, 7, 12, 13, sample.len() - 1] {
+            assert!(
+                matches!(
+                    decode_top_down(&sample[..end]),
+                    Err(SmoothGridError::UnexpectedEof)
+                ),
+                "cut at {}",
+                end
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_runs_past_the_end_of_a_chunk() {
+        assert!(matches!(
+            decode_top_down(&[1, 1, 0, GRASS | COUNT_FLAG, 8]),
+            Err(SmoothGridError::RunOverflow)
+        ));
+        assert!(matches!(
+            decode_top_down(&[1, 1, 0, GRASS | COUNT_FLAG, 3, COUNT_FLAG, 4]),
+            Err(SmoothGridError::RunOverflow)
+        ));
+    }
+}